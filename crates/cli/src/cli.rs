@@ -1,11 +1,38 @@
-use clap::{Parser, Subcommand, ValueEnum};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+    /// If set, entries that cannot be processed are skipped and listed instead of aborting the run.
+    #[arg(long, global = true, default_value_t = false)]
+    pub keep_going: bool,
+    /// The directory where the parsed input files are cached, overriding the config file.
+    #[arg(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+    /// If set, the parsed input files are cached even if the config file disables the cache.
+    #[arg(
+        long,
+        global = true,
+        default_value_t = false,
+        overrides_with = "no_cache"
+    )]
+    pub cache: bool,
+    /// If set, the input files are always parsed from scratch and not cached.
+    #[arg(long, global = true, default_value_t = false, overrides_with = "cache")]
+    pub no_cache: bool,
+    /// The path to the config file. Defaults to `jadata.toml` in the current directory if it exists.
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// The languages of the meanings to include, overriding the config file.
+    #[arg(long, global = true, value_delimiter = ',')]
+    pub languages: Vec<String>,
+    /// The path to a JSON overlay with manual corrections to apply to the generated files, overriding the config file.
+    #[arg(long, global = true)]
+    pub overlay: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -14,43 +41,45 @@ pub enum Command {
     Kanjifile {
         /// The version of the generated kanjifile.
         #[arg(short, long)]
-        version: String,
+        version: Option<String>,
         /// The path to the input KANJIDIC2 file,
         #[arg(short = 'd', long)]
-        kanjidic: PathBuf,
+        kanjidic: Option<PathBuf>,
         /// The path to the input KRADFILE.
         #[arg(short, long)]
-        kradfile: PathBuf,
+        kradfile: Option<PathBuf>,
         /// The path to the kanjifile_skeleton.json file.
         #[arg(short, long)]
-        skeleton: PathBuf,
-        /// The path to the output kanjifile.
+        skeleton: Option<PathBuf>,
+        /// The path to the output kanjifile. Defaults to a file in the configured output directory.
         #[arg(short, long)]
-        output: PathBuf,
-        /// The format of the output file.
-        #[arg(short = 't', long)]
-        format: Format,
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        options: OutputArgs,
+        /// The path to write a report of the kanji names that collided to,
+        /// as Markdown if it ends in `.md` and as JSON otherwise.
+        #[arg(long)]
+        report: Option<PathBuf>,
     },
     /// Generates the wordfile.
     Wordfile {
         /// The version of the generated wordfile.
         #[arg(short, long)]
-        version: String,
+        version: Option<String>,
         /// The path to the input JMdict file.
         #[arg(short, long)]
-        jmdict: PathBuf,
+        jmdict: Option<PathBuf>,
         /// The path to the input JMdict furigana file.
         #[arg(short, long)]
-        furigana: PathBuf,
+        furigana: Option<PathBuf>,
         /// The path to the wordfile_skeleton.json file.
         #[arg(short, long)]
-        skeleton: PathBuf,
-        /// The path to the output wordfile.
+        skeleton: Option<PathBuf>,
+        /// The path to the output wordfile. Defaults to a file in the configured output directory.
         #[arg(short, long)]
-        output: PathBuf,
-        /// The format of the output file.
-        #[arg(short = 't', long)]
-        format: Format,
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        options: OutputArgs,
     },
     /// Generates the kanjifile skeleton.
     KanjifileSkeleton {
@@ -59,16 +88,19 @@ pub enum Command {
         clean: bool,
         /// The path to the input KANJIDIC2 file,
         #[arg(short = 'd', long)]
-        kanjidic: PathBuf,
+        kanjidic: Option<PathBuf>,
         /// The path to the input JMdict file.
         // JMdict contains kanji that are unfortunately not in the KANJIDIC2,
         // so to make sure we don't miss any we go through all the written forms
         // of the JMdict to check for missing kanji
         #[arg(short, long)]
-        jmdict: PathBuf,
+        jmdict: Option<PathBuf>,
         /// The path to the output kanjifile skeleton.
         #[arg(short, long)]
-        output: PathBuf,
+        output: Option<PathBuf>,
+        /// The path to write a report of the changes to, as Markdown if it ends in `.md` and as JSON otherwise.
+        #[arg(long)]
+        report: Option<PathBuf>,
     },
     /// Generates the wordfile skeleton.
     WordfileSkeleton {
@@ -77,18 +109,275 @@ pub enum Command {
         clean: bool,
         /// The path to the input JMdict file.
         #[arg(short, long)]
-        jmdict: PathBuf,
+        jmdict: Option<PathBuf>,
+        /// The path to a JSON file with manual overrides for the successors of removed words.
+        #[arg(long)]
+        successors: Option<PathBuf>,
+        /// The path to a JSON file with manually curated words that are not in JMdict.
+        #[arg(long)]
+        curated_words: Option<PathBuf>,
         /// The path to the output wordfile skeleton.
         #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// The path to write a report of the changes to, as Markdown if it ends in `.md` and as JSON otherwise.
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Imports kanji names and similar kanji into the kanjifile skeleton from CSV or TSV files.
+    /// Files ending in `.tsv` are tab-separated, others comma-separated.
+    KanjiAnnotate {
+        /// A table with a kanji and its name on each row.
+        #[arg(short, long)]
+        names: Option<PathBuf>,
+        /// A table with a group of similar kanji on each row. Each kanji is marked as similar to the others in the row.
+        #[arg(long)]
+        similar: Option<PathBuf>,
+        /// If set, imported names replace conflicting existing ones instead of being reported as errors.
+        #[arg(long, default_value_t = false)]
+        overwrite: bool,
+        /// The path to the kanjifile skeleton.
+        #[arg(short, long)]
+        skeleton: Option<PathBuf>,
+    },
+    /// Proposes candidates for similar kanji based on their KRADFILE components and KANJIDIC2 stroke counts.
+    /// The candidates are written to a review table that can be imported with `kanji-annotate --similar`.
+    SimilarCandidates {
+        /// The path to the input KANJIDIC2 file.
+        #[arg(short = 'd', long)]
+        kanjidic: Option<PathBuf>,
+        /// The path to the input KRADFILE.
+        #[arg(short, long)]
+        kradfile: Option<PathBuf>,
+        /// The path to the kanjifile skeleton.
+        #[arg(short, long)]
+        skeleton: Option<PathBuf>,
+        /// The path to the review table, tab-separated if it ends in `.tsv` and comma-separated otherwise.
+        #[arg(short, long, default_value = "similar_candidates.tsv")]
         output: PathBuf,
+        /// The smallest share of their components that two kanji need to have in common, from 0 to 1.
+        #[arg(long, default_value_t = 0.6)]
+        min_overlap: f64,
+        /// The largest difference between the stroke counts of two kanji.
+        #[arg(long, default_value_t = 2)]
+        max_stroke_difference: u32,
+        /// The number of the best candidates proposed for each kanji.
+        #[arg(long, default_value_t = 3)]
+        per_kanji: usize,
+    },
+    /// Optionally updates the skeletons, then generates the kanjifile and wordfile in one go.
+    Build(BuildArgs),
+    /// Validates generated files and prints all problems found in them.
+    Validate {
+        /// The path to the kanjifile to validate.
+        #[arg(short, long)]
+        kanjifile: Option<PathBuf>,
+        /// The path to the wordfile to validate.
+        #[arg(short, long)]
+        wordfile: Option<PathBuf>,
+        /// The format of both files, if not detected from their extensions.
+        #[arg(short = 't', long)]
+        format: Option<Format>,
+        /// The format of the kanjifile, overriding `--format`.
+        #[arg(long)]
+        kanjifile_format: Option<Format>,
+        /// The format of the wordfile, overriding `--format`.
+        #[arg(long)]
+        wordfile_format: Option<Format>,
+    },
+    /// Compares two versions of a generated file and prints the changes between them.
+    /// Entries are matched by their ids.
+    Diff {
+        /// The kind of the compared files.
+        #[arg(short, long)]
+        file: FileKind,
+        /// The path to the old file.
+        old: PathBuf,
+        /// The path to the new file.
+        new: PathBuf,
+        /// The format of both files, if not detected from their extensions.
+        #[arg(short = 't', long)]
+        format: Option<Format>,
+        /// The format of the old file, overriding `--format`.
+        #[arg(long)]
+        old_format: Option<Format>,
+        /// The format of the new file, overriding `--format`.
+        #[arg(long)]
+        new_format: Option<Format>,
+        /// If set, the changes are printed as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Manages the config file.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
     },
 }
 
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Writes a commented config file template listing all the keys and their defaults.
+    Init {
+        /// If set, an existing config file is overwritten.
+        #[arg(short, long, default_value_t = false)]
+        force: bool,
+    },
+}
+
+/// Arguments for the `build` command.
+/// Any values not given are taken from the config file.
+#[derive(Args)]
+pub struct BuildArgs {
+    /// If set, the skeletons are updated from the input files before generating the outputs.
+    #[arg(short, long, default_value_t = false)]
+    pub update_skeletons: bool,
+    /// The directory to write the report of the kanji names that collided to, in both JSON and Markdown.
+    /// If the skeletons are updated, the reports of the updates are written there too.
+    #[arg(long)]
+    pub report_dir: Option<PathBuf>,
+    /// The version of the generated kanjifile.
+    #[arg(long)]
+    pub kanjifile_version: Option<String>,
+    /// The version of the generated wordfile.
+    #[arg(long)]
+    pub wordfile_version: Option<String>,
+    /// The path to the input KANJIDIC2 file.
+    #[arg(long)]
+    pub kanjidic: Option<PathBuf>,
+    /// The path to the input KRADFILE.
+    #[arg(long)]
+    pub kradfile: Option<PathBuf>,
+    /// The path to the input JMdict file.
+    #[arg(long)]
+    pub jmdict: Option<PathBuf>,
+    /// The path to the input JMdict furigana file.
+    #[arg(long)]
+    pub furigana: Option<PathBuf>,
+    /// The path to the kanjifile_skeleton.json file.
+    #[arg(long)]
+    pub kanjifile_skeleton: Option<PathBuf>,
+    /// The path to the wordfile_skeleton.json file.
+    #[arg(long)]
+    pub wordfile_skeleton: Option<PathBuf>,
+    /// The path to a JSON file with manual overrides for the successors of removed words.
+    #[arg(long)]
+    pub word_successors: Option<PathBuf>,
+    /// The path to a JSON file with manually curated words that are not in JMdict.
+    #[arg(long)]
+    pub curated_words: Option<PathBuf>,
+    /// The directory the kanjifile and wordfile are written to.
+    #[arg(short, long)]
+    pub output_dir: Option<PathBuf>,
+    /// The formats of the output files. Each file is written once in every given format.
+    #[arg(short = 't', long = "format", value_delimiter = ',')]
+    pub formats: Vec<Format>,
+    /// The compression to apply to the output files, if any.
+    #[arg(short = 'z', long)]
+    pub compression: Option<Compression>,
+    /// If set, JSON output is written without any whitespace.
+    /// `--compact=false` pretty-prints it even if the config file sets `output.compact`.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub compact: Option<bool>,
+}
+
+/// Options for writing the resulting file.
+/// Any values not given are taken from the config file.
+#[derive(Clone, Copy, Args)]
+pub struct OutputArgs {
+    /// The format of the output file. Defaults to the first configured format.
+    #[arg(short = 't', long)]
+    pub format: Option<Format>,
+    /// The compression to apply to the output file, if any.
+    #[arg(short = 'z', long)]
+    pub compression: Option<Compression>,
+    /// If set, JSON output is written without any whitespace. Has no effect on other formats.
+    /// `--compact=false` pretty-prints it even if the config file sets `output.compact`.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub compact: Option<bool>,
+}
+
+/// The kinds of generated files.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FileKind {
+    Kanjifile,
+    Wordfile,
+}
+
 /// Alternative formats for the resulting file.
-#[derive(Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Format {
     /// A verbose human-readable and -writable format.
     Json,
     /// A concise binary format. See https://crates.io/crates/postcard.
     Postcard,
+    /// The postcard layout of older versions of jadata, for consumers that have not been updated.
+    /// Leaves out the data added since, and fails if any of the ids do not fit in the old id types.
+    LegacyPostcard,
+}
+
+impl Format {
+    /// The file extension for files in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Postcard => "postcard",
+            Self::LegacyPostcard => "legacy.postcard",
+        }
+    }
+
+    /// Detects the format of a file from its extension, ignoring the extension of any compression.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let name = [Compression::Gzip, Compression::Zstd]
+            .into_iter()
+            .find_map(|c| name.strip_suffix(c.extension())?.strip_suffix('.'))
+            .unwrap_or(name);
+        // the legacy extension ends with the postcard one, so it is checked first
+        [Self::LegacyPostcard, Self::Postcard, Self::Json]
+            .into_iter()
+            .find(|format| {
+                name.strip_suffix(format.extension())
+                    .is_some_and(|stem| stem.ends_with('.'))
+            })
+    }
+}
+
+/// Alternative compression formats for the resulting file.
+#[derive(Debug, Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Widely supported, can be served directly to browsers. See https://www.gzip.org/.
+    Gzip,
+    /// Compresses better and decompresses faster than gzip. See https://facebook.github.io/zstd/.
+    Zstd,
+}
+
+impl Compression {
+    /// The file extension added to files with this compression.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_format() {
+        let detect = |path: &str| Format::detect(Path::new(path));
+        assert_eq!(detect("out/wordfile.json"), Some(Format::Json));
+        assert_eq!(detect("out/wordfile.postcard.gz"), Some(Format::Postcard));
+        assert_eq!(
+            detect("kanjifile.legacy.postcard.zst"),
+            Some(Format::LegacyPostcard)
+        );
+        assert_eq!(detect("wordfile.gz"), None);
+        assert_eq!(detect("json"), None);
+        assert_eq!(detect("wordfile"), None);
+    }
 }
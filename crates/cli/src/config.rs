@@ -0,0 +1,386 @@
+//! The `jadata.toml` configuration file.
+//! Every value has a default matching the layout of the repository, so the file and all of its keys are optional.
+//! Values given on the command line take precedence over the ones in the file.
+
+use crate::{
+    cli::{BuildArgs, Cli, Compression, Format, OutputArgs},
+    output::OutputOptions,
+};
+use eyre::{ContextCompat, WrapErr};
+use serde::Deserialize;
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// The path the configuration file is read from if no other path is given.
+pub const DEFAULT_PATH: &str = "jadata.toml";
+
+/// The template written by `config init`. Parses into the default configuration.
+pub const TEMPLATE: &str = r#"# Configuration for jadata-cli.
+# All keys are optional, the commented out values are the defaults.
+# Values given on the command line take precedence over the ones in this file.
+
+[sources]
+# The path to the KANJIDIC2 file.
+#kanjidic = "./external/kanjidic2.xml"
+# The path to the KRADFILE.
+#kradfile = "./external/kradfile"
+# The path to the JMdict file.
+#jmdict = "./external/JMdict_e_examp.xml"
+# The path to the JMdict furigana file.
+#furigana = "./external/JmdictFuriganaPretty.json"
+
+[skeletons]
+# The path to the kanjifile skeleton.
+#kanjifile = "./included/kanjifile_skeleton.json"
+# The path to the wordfile skeleton.
+#wordfile = "./included/wordfile_skeleton.json"
+# The path to a JSON file with manual overrides for the successors of words removed from the wordfile skeleton,
+# such as [{ "id": 123, "written_form": "新しい" }]. A null written form prevents the word from having a successor.
+# Has no default, the successors are only detected automatically if not set.
+#word_successors = "./included/word_successors.json"
+# The path to a JSON file with manually curated words that are not in JMdict, added to the wordfile skeleton on updates,
+# such as [{ "written_forms": ["ググる"], "meanings": ["to google"], "readings": [{ "reading": "ググる" }] }].
+# Curated words already in the skeleton are matched by their written forms and replaced.
+# Has no default, the curated words already in the skeleton are kept as they are if not set.
+#curated_words = "./included/curated_words.json"
+# The path to a JSON overlay with manual corrections applied to the generated files after filling the skeletons,
+# such as { "words": { "123": { "meanings": [{ "append": "to draw" }] } } }.
+# Each field can be changed with "set", "append" and "remove" operations, which are applied in order.
+# Has no default, no corrections are applied if not set.
+#overlay = "./included/overlay.json"
+
+[versions]
+# The versions of the generated files. Have no default and must be given here or on the command line.
+#kanjifile = "1.0.0"
+#wordfile = "1.0.0"
+
+[output]
+# The directory the generated files are written to.
+#dir = "./generated"
+# The formats the generated files are written in, "json", "postcard" and/or "legacy-postcard".
+# "legacy-postcard" is the postcard layout of older versions of jadata, for consumers that have not been updated.
+#formats = ["json", "postcard"]
+# The compression applied to the generated files, "gzip" or "zstd". Uncompressed if not set.
+#compression = "gzip"
+# If set, JSON output is written without any whitespace.
+#compact = false
+
+[cache]
+# If set, the parsed input files are cached and reused by later commands.
+#enabled = false
+# The directory the parsed input files are cached in. The cache can be cleared by removing the directory.
+#dir = "./.cache"
+
+[filters]
+# The languages of the meanings included in the generated files, as ISO 639-2 codes such as "eng" or "fre".
+# The JMdict_e files only contain English meanings.
+#languages = ["eng"]
+# Senses with any of these JMdict misc codes such as "arch" (archaic) or "obs" (obsolete) contribute no meanings to words.
+#exclude_misc = []
+"#;
+
+/// The contents of a configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub sources: Sources,
+    pub skeletons: Skeletons,
+    pub versions: Versions,
+    pub output: Output,
+    pub cache: Cache,
+    pub filters: Filters,
+}
+
+impl Config {
+    /// Reads the configuration from the given path, or from `DEFAULT_PATH` if it exists.
+    pub fn load(path: Option<&Path>) -> eyre::Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path, true),
+            None => (Path::new(DEFAULT_PATH), false),
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound && !explicit => {
+                tracing::debug!("no config file at '{}', using defaults", path.display());
+                return Ok(Self::default());
+            }
+            Err(err) => {
+                return Err(err).wrap_err_with(|| {
+                    format!("Failed to read config file at '{}'", path.display())
+                })
+            }
+        };
+        let config = toml::from_str(&contents)
+            .wrap_err_with(|| format!("Invalid config file at '{}'", path.display()))?;
+        tracing::info!("using config file at '{}'", path.display());
+        Ok(config)
+    }
+
+    /// Writes the commented template to the given path. Existing files are only overwritten if `force` is set.
+    pub fn init(path: &Path, force: bool) -> eyre::Result<()> {
+        if path.exists() && !force {
+            eyre::bail!(
+                "A file already exists at '{}', use --force to overwrite it",
+                path.display()
+            );
+        }
+        fs::write(path, TEMPLATE)
+            .wrap_err_with(|| format!("Failed to write config file at '{}'", path.display()))?;
+        Ok(())
+    }
+
+    /// Overrides the configured values with the global flags given to every command.
+    pub fn override_with_globals(&mut self, cli: &mut Cli) {
+        if !cli.languages.is_empty() {
+            self.filters.languages = std::mem::take(&mut cli.languages);
+        }
+        if let Some(dir) = cli.cache_dir.take() {
+            self.cache.dir = dir;
+        }
+        if cli.cache {
+            self.cache.enabled = true;
+        }
+        if cli.no_cache {
+            self.cache.enabled = false;
+        }
+        if let Some(overlay) = cli.overlay.take() {
+            self.skeletons.overlay = Some(overlay);
+        }
+    }
+
+    /// Overrides the configured values with the ones given to the `build` command.
+    pub fn override_with(&mut self, args: BuildArgs) {
+        fn set<T>(value: &mut T, arg: Option<T>) {
+            if let Some(arg) = arg {
+                *value = arg;
+            }
+        }
+        set(
+            &mut self.versions.kanjifile,
+            args.kanjifile_version.map(Some),
+        );
+        set(&mut self.versions.wordfile, args.wordfile_version.map(Some));
+        set(&mut self.sources.kanjidic, args.kanjidic);
+        set(&mut self.sources.kradfile, args.kradfile);
+        set(&mut self.sources.jmdict, args.jmdict);
+        set(&mut self.sources.furigana, args.furigana);
+        set(&mut self.skeletons.kanjifile, args.kanjifile_skeleton);
+        set(&mut self.skeletons.wordfile, args.wordfile_skeleton);
+        set(
+            &mut self.skeletons.word_successors,
+            args.word_successors.map(Some),
+        );
+        set(
+            &mut self.skeletons.curated_words,
+            args.curated_words.map(Some),
+        );
+        set(&mut self.output.dir, args.output_dir);
+        if !args.formats.is_empty() {
+            self.output.formats = args.formats;
+        }
+        set(&mut self.output.compression, args.compression.map(Some));
+        set(&mut self.output.compact, args.compact);
+    }
+}
+
+/// Paths to the input files.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sources {
+    pub kanjidic: PathBuf,
+    pub kradfile: PathBuf,
+    pub jmdict: PathBuf,
+    pub furigana: PathBuf,
+}
+
+impl Default for Sources {
+    fn default() -> Self {
+        Self {
+            kanjidic: PathBuf::from("./external/kanjidic2.xml"),
+            kradfile: PathBuf::from("./external/kradfile"),
+            jmdict: PathBuf::from("./external/JMdict_e_examp.xml"),
+            furigana: PathBuf::from("./external/JmdictFuriganaPretty.json"),
+        }
+    }
+}
+
+/// Paths to the skeleton files.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Skeletons {
+    pub kanjifile: PathBuf,
+    pub wordfile: PathBuf,
+    pub word_successors: Option<PathBuf>,
+    pub curated_words: Option<PathBuf>,
+    pub overlay: Option<PathBuf>,
+}
+
+impl Default for Skeletons {
+    fn default() -> Self {
+        Self {
+            kanjifile: PathBuf::from("./included/kanjifile_skeleton.json"),
+            wordfile: PathBuf::from("./included/wordfile_skeleton.json"),
+            word_successors: None,
+            curated_words: None,
+            overlay: None,
+        }
+    }
+}
+
+/// Versions of the generated files.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Versions {
+    pub kanjifile: Option<String>,
+    pub wordfile: Option<String>,
+}
+
+impl Versions {
+    /// The version of the kanjifile, which has no default.
+    pub fn kanjifile(&self) -> eyre::Result<String> {
+        self.kanjifile.clone().wrap_err(
+            "No kanjifile version given, set it on the command line or with versions.kanjifile in the config file",
+        )
+    }
+
+    /// The version of the wordfile, which has no default.
+    pub fn wordfile(&self) -> eyre::Result<String> {
+        self.wordfile.clone().wrap_err(
+            "No wordfile version given, set it on the command line or with versions.wordfile in the config file",
+        )
+    }
+}
+
+/// Settings for the generated files.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Output {
+    pub dir: PathBuf,
+    pub formats: Vec<Format>,
+    pub compression: Option<Compression>,
+    pub compact: bool,
+}
+
+impl Output {
+    /// The options for writing a single file, overriding the configured ones with the given arguments.
+    /// Without an explicit format, the first configured one is used.
+    pub fn options(&self, args: OutputArgs) -> eyre::Result<OutputOptions> {
+        let format = args
+            .format
+            .or_else(|| self.formats.first().copied())
+            .wrap_err("No output format given, set it on the command line or with output.formats in the config file")?;
+        Ok(OutputOptions {
+            format,
+            compression: args.compression.or(self.compression),
+            compact: args.compact.unwrap_or(self.compact),
+        })
+    }
+
+    /// The options for writing the files in every configured format.
+    pub fn all_options(&self) -> impl Iterator<Item = OutputOptions> + '_ {
+        self.formats.iter().map(|&format| OutputOptions {
+            format,
+            compression: self.compression,
+            compact: self.compact,
+        })
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("./generated"),
+            formats: vec![Format::Json, Format::Postcard],
+            compression: None,
+            compact: false,
+        }
+    }
+}
+
+/// Settings for the cache of parsed input files.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cache {
+    pub enabled: bool,
+    pub dir: PathBuf,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("./.cache"),
+        }
+    }
+}
+
+/// Filters for the contents of the generated files.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filters {
+    pub languages: Vec<String>,
+    pub exclude_misc: Vec<String>,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Self {
+            languages: vec!["eng".to_string()],
+            exclude_misc: vec![],
+        }
+    }
+}
+
+impl Filters {
+    /// Checks whether meanings in the given language are included.
+    /// Accepts both the ISO 639-2 codes used by JMdict and the ISO 639-1 codes used by KANJIDIC2,
+    /// a missing language means English in both.
+    pub fn includes_language(&self, lang: Option<&str>) -> bool {
+        let lang = match lang.unwrap_or("eng") {
+            // the languages in KANJIDIC2 besides English
+            "en" => "eng",
+            "fr" => "fre",
+            "es" => "spa",
+            "pt" => "por",
+            other => other,
+        };
+        self.languages.iter().any(|l| l == lang)
+    }
+
+    /// Checks whether a sense with the given misc codes contributes meanings.
+    pub fn includes_sense<'a>(&self, mut misc: impl Iterator<Item = &'a str>) -> bool {
+        !misc.any(|code| self.exclude_misc.iter().any(|e| e == code))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn template_is_default() {
+        let config: Config = toml::from_str(TEMPLATE).unwrap();
+        let default = Config::default();
+        assert_eq!(config.sources.jmdict, default.sources.jmdict);
+        assert_eq!(config.output.formats, default.output.formats);
+        assert_eq!(config.output.compact, default.output.compact);
+        assert_eq!(config.cache.enabled, default.cache.enabled);
+        assert_eq!(config.cache.dir, default.cache.dir);
+    }
+
+    #[test]
+    fn compact_flag_overrides_config() {
+        let output: Output = toml::from_str("compact = true").unwrap();
+        let args = |compact| OutputArgs {
+            format: Some(Format::Json),
+            compression: None,
+            compact,
+        };
+        assert!(output.options(args(None)).unwrap().compact);
+        assert!(output.options(args(Some(true))).unwrap().compact);
+        assert!(!output.options(args(Some(false))).unwrap().compact);
+    }
+}
@@ -0,0 +1,359 @@
+//! Compares two versions of a generated file.
+//! Entries are matched by their stable ids and compared field by field,
+//! so the same comparison works for any file whose entries can be serialized.
+
+use jadata::{
+    kanjifile::{Kanji, Kanjifile},
+    wordfile::{Word, Wordfile},
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+};
+
+/// The differences between two versions of a generated file.
+#[derive(Debug, Default, Serialize)]
+pub struct Diff {
+    pub summary: Summary,
+    /// The changed fields of the header.
+    pub header: Vec<FieldChange>,
+    /// The changed list fields of the header, such as the tombstones of the wordfile.
+    /// They can grow long, so only the number of items added and removed is given.
+    pub header_lists: Vec<ListChange>,
+    /// The entries only found in the new file.
+    pub added: Vec<Entry>,
+    /// The entries only found in the old file.
+    pub removed: Vec<Entry>,
+    /// The entries found in both files with different contents.
+    pub changed: Vec<ChangedEntry>,
+}
+
+/// The number of differences of each kind.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// The number of changed entries for each field.
+    pub changed_fields: BTreeMap<String, usize>,
+}
+
+/// An entry identified by its id and a short label such as the kanji or the written forms of the word.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub id: u32,
+    pub label: String,
+}
+
+/// An entry with changed fields.
+#[derive(Debug, Serialize)]
+pub struct ChangedEntry {
+    pub id: u32,
+    pub label: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// A field with a different value in each file.
+/// Fields that are missing from one of the files, such as empty lists, have the value `null`.
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// A list with items that are only found in one of the files.
+#[derive(Debug, Serialize)]
+pub struct ListChange {
+    pub field: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Compares two kanjifiles.
+pub fn kanjifiles(old: &Kanjifile, new: &Kanjifile) -> eyre::Result<Diff> {
+    let label = |k: &Kanji| (k.id.0, k.kanji.clone());
+    diff(
+        &old.header,
+        &new.header,
+        old.kanji.iter().map(|k| (label(k), k)),
+        new.kanji.iter().map(|k| (label(k), k)),
+    )
+}
+
+/// Compares two wordfiles.
+pub fn wordfiles(old: &Wordfile, new: &Wordfile) -> eyre::Result<Diff> {
+    let label = |w: &Word| (w.id.0, w.written_forms.join(", "));
+    diff(
+        &old.header,
+        &new.header,
+        old.words.iter().map(|w| (label(w), w)),
+        new.words.iter().map(|w| (label(w), w)),
+    )
+}
+
+fn diff<'a, H: Serialize, T: Serialize + 'a>(
+    old_header: &H,
+    new_header: &H,
+    old: impl Iterator<Item = ((u32, String), &'a T)>,
+    new: impl Iterator<Item = ((u32, String), &'a T)>,
+) -> eyre::Result<Diff> {
+    let mut diff = Diff::default();
+    for change in fields(&to_object(old_header)?, &to_object(new_header)?) {
+        match (items(&change.old), items(&change.new)) {
+            (Some(old), Some(new)) => diff.header_lists.push(ListChange {
+                field: change.field,
+                added: new.difference(&old).count(),
+                removed: old.difference(&new).count(),
+            }),
+            _ => diff.header.push(change),
+        }
+    }
+
+    let mut old = old
+        .map(|((id, label), entry)| Ok((id, (label, to_object(entry)?))))
+        .collect::<eyre::Result<BTreeMap<_, _>>>()?;
+    for ((id, label), entry) in new {
+        let Some((_, old_entry)) = old.remove(&id) else {
+            diff.added.push(Entry { id, label });
+            continue;
+        };
+        let changes = fields(&old_entry, &to_object(entry)?);
+        if !changes.is_empty() {
+            for change in &changes {
+                *diff
+                    .summary
+                    .changed_fields
+                    .entry(change.field.clone())
+                    .or_default() += 1;
+            }
+            diff.changed.push(ChangedEntry { id, label, changes });
+        }
+    }
+    diff.removed = old
+        .into_iter()
+        .map(|(id, (label, _))| Entry { id, label })
+        .collect();
+
+    diff.added.sort_by_key(|e| e.id);
+    diff.changed.sort_by_key(|e| e.id);
+    diff.summary.added = diff.added.len();
+    diff.summary.removed = diff.removed.len();
+    diff.summary.changed = diff.changed.len();
+    Ok(diff)
+}
+
+// the human-readable serialization is used so that the fields have names
+fn to_object(value: &impl Serialize) -> eyre::Result<Map<String, Value>> {
+    match serde_json::to_value(value)? {
+        Value::Object(map) => Ok(map),
+        other => eyre::bail!("Expected an object, found {other}"),
+    }
+}
+
+// the fields that differ between the two objects, in alphabetical order
+fn fields(old: &Map<String, Value>, new: &Map<String, Value>) -> Vec<FieldChange> {
+    let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = old.get(key).unwrap_or(&Value::Null);
+            let new = new.get(key).unwrap_or(&Value::Null);
+            (old != new).then(|| FieldChange {
+                field: key.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            })
+        })
+        .collect()
+}
+
+// the items of a list field, which is missing from the file if the list is empty
+fn items(value: &Value) -> Option<BTreeSet<String>> {
+    match value {
+        Value::Array(items) => Some(items.iter().map(Value::to_string).collect()),
+        Value::Null => Some(BTreeSet::new()),
+        _ => None,
+    }
+}
+
+// a line per change, similar to a unified diff
+impl Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.header.is_empty() || !self.header_lists.is_empty() {
+            writeln!(f, "header")?;
+            for change in &self.header {
+                writeln!(f, "  {change}")?;
+            }
+            for change in &self.header_lists {
+                writeln!(
+                    f,
+                    "  {}: {} added, {} removed",
+                    change.field, change.added, change.removed
+                )?;
+            }
+        }
+        for entry in &self.added {
+            writeln!(f, "+ {} {}", entry.id, entry.label)?;
+        }
+        for entry in &self.removed {
+            writeln!(f, "- {} {}", entry.id, entry.label)?;
+        }
+        for entry in &self.changed {
+            writeln!(f, "~ {} {}", entry.id, entry.label)?;
+            for change in &entry.changes {
+                writeln!(f, "  {change}")?;
+            }
+        }
+        let summary = &self.summary;
+        write!(
+            f,
+            "{} added, {} removed, {} changed",
+            summary.added, summary.removed, summary.changed
+        )?;
+        for (field, count) in &summary.changed_fields {
+            write!(f, "\n  {field}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jadata::{
+        id::{JmdictSeq, WordId},
+        wordfile::{Header, Tombstone},
+    };
+    use serde_json::json;
+
+    fn wordfile(last_word_id: u32, tombstones: &[u32], words: &[(u32, &str, &[&str])]) -> Wordfile {
+        Wordfile {
+            header: Header {
+                version: "1".to_string(),
+                jmdict_version: "1".to_string(),
+                jmdict_date_of_creation: "2023-12-01".to_string(),
+                last_word_id: WordId(last_word_id),
+                tombstones: tombstones
+                    .iter()
+                    .map(|&id| Tombstone {
+                        id: WordId(id),
+                        jmdict_id: None,
+                        written_forms: vec!["舟".to_string()],
+                        removed_in_jmdict_version: "1".to_string(),
+                        successor_id: None,
+                    })
+                    .collect(),
+            },
+            words: words
+                .iter()
+                .map(|(id, written_form, meanings)| Word {
+                    id: WordId(*id),
+                    jmdict_id: Some(JmdictSeq(1000000 + id)),
+                    written_forms: vec![written_form.to_string()],
+                    meanings: meanings.iter().map(|m| m.to_string()).collect(),
+                    readings: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    // 舟 is removed, 書 is added and the meanings of 船 change
+    fn example() -> Diff {
+        let old = wordfile(
+            3,
+            &[],
+            &[
+                (1, "船", &["ship"]),
+                (2, "舟", &["boat"]),
+                (3, "本", &["book"]),
+            ],
+        );
+        let new = wordfile(
+            4,
+            &[],
+            &[
+                (1, "船", &["ship", "boat"]),
+                (3, "本", &["book"]),
+                (4, "書", &["writing"]),
+            ],
+        );
+        wordfiles(&old, &new).unwrap()
+    }
+
+    #[test]
+    fn added_removed_and_changed_entries() {
+        let diff = example();
+        assert_eq!(
+            diff.to_string(),
+            "\
+header
+  last_word_id: 3 -> 4
++ 4 書
+- 2 舟
+~ 1 船
+  meanings: [\"ship\"] -> [\"ship\",\"boat\"]
+1 added, 1 removed, 1 changed
+  meanings: 1"
+        );
+    }
+
+    #[test]
+    fn json_shape() {
+        let diff = serde_json::to_value(example()).unwrap();
+        assert_eq!(
+            diff,
+            json!({
+                "summary": {
+                    "added": 1,
+                    "removed": 1,
+                    "changed": 1,
+                    "changed_fields": { "meanings": 1 },
+                },
+                "header": [{ "field": "last_word_id", "old": 3, "new": 4 }],
+                "header_lists": [],
+                "added": [{ "id": 4, "label": "書" }],
+                "removed": [{ "id": 2, "label": "舟" }],
+                "changed": [{
+                    "id": 1,
+                    "label": "船",
+                    "changes": [{ "field": "meanings", "old": ["ship"], "new": ["ship", "boat"] }],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn unchanged_files_have_no_differences() {
+        let wordfile = wordfile(1, &[], &[(1, "船", &["ship"])]);
+        let diff = wordfiles(&wordfile, &wordfile).unwrap();
+        assert_eq!(diff.to_string(), "0 added, 0 removed, 0 changed");
+    }
+
+    #[test]
+    fn tombstones_are_summarized() {
+        let old = wordfile(3, &[2], &[(1, "船", &["ship"])]);
+        let new = wordfile(4, &[2, 3, 4], &[(1, "船", &["ship"])]);
+        let diff = wordfiles(&old, &new).unwrap();
+        assert_eq!(diff.header.len(), 1);
+        assert_eq!(diff.header_lists.len(), 1);
+        let tombstones = &diff.header_lists[0];
+        assert_eq!(tombstones.field, "tombstones");
+        assert_eq!((tombstones.added, tombstones.removed), (2, 0));
+        assert_eq!(
+            diff.to_string(),
+            "\
+header
+  last_word_id: 3 -> 4
+  tombstones: 2 added, 0 removed
+0 added, 0 removed, 0 changed"
+        );
+    }
+}
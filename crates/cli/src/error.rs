@@ -0,0 +1,146 @@
+//! Errors encountered while processing the input files.
+
+use jadata::id::{JmdictSeq, KanjiId, WordId};
+use std::fmt::{self, Display};
+
+/// A problem with a single entry in the input files.
+/// The entry is skipped so that the rest of the input can still be checked.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// A KANJIDIC2 kanji has no corresponding entry in the kanjifile skeleton.
+    MissingKanjiSkeleton { kanji: String },
+    /// A KANJIDIC2 literal consists of more than one codepoint.
+    MultiCodepointLiteral { literal: String },
+    /// A kanji appears multiple times in KANJIDIC2.
+    RepeatedKanji { kanji: String },
+    /// Multiple kanji in the kanjifile skeleton share an id.
+    RepeatedKanjiId { id: KanjiId, kanji: String },
+    /// A JMdict entry has no corresponding words in the wordfile skeleton.
+    MissingJmdictId { jmdict_id: JmdictSeq },
+    /// A JMdict entry has an `ent_seq` that is not a valid id.
+    InvalidEntSeq { ent_seq: String },
+    /// A successor override names a written form that is not among the new words.
+    UnknownSuccessorOverride { id: WordId, written_form: String },
+    /// An overlay refers to an entry that is not in the generated file.
+    UnknownOverlayId { kind: &'static str, id: String },
+    /// An overlay change cannot be applied to its entry.
+    InvalidOverlay { entry: String, reason: String },
+    /// A row of an annotation table does not have the expected cells.
+    InvalidAnnotation { row: String, reason: &'static str },
+    /// An annotation refers to a kanji that is not in the kanjifile skeleton.
+    UnknownAnnotatedKanji { kanji: String },
+    /// Multiple kanji in the kanjifile skeleton share a name.
+    RepeatedKanjiName {
+        name: String,
+        kanji: String,
+        other: String,
+    },
+    /// An imported kanji name differs from the existing one.
+    ConflictingKanjiName {
+        kanji: String,
+        existing: String,
+        new: String,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingKanjiSkeleton { kanji } => write!(f, "no skeleton for kanji '{kanji}'"),
+            Self::MultiCodepointLiteral { literal } => {
+                write!(f, "multi-codepoint literal '{literal}'")
+            }
+            Self::RepeatedKanji { kanji } => write!(f, "repeated kanji '{kanji}'"),
+            Self::RepeatedKanjiId { id, kanji } => {
+                write!(f, "repeated kanji id {id} for kanji '{kanji}'")
+            }
+            Self::MissingJmdictId { jmdict_id } => {
+                write!(f, "no skeleton for JMdict entry {jmdict_id}")
+            }
+            Self::InvalidEntSeq { ent_seq } => write!(f, "invalid JMdict ent_seq '{ent_seq}'"),
+            Self::UnknownSuccessorOverride { id, written_form } => write!(
+                f,
+                "the successor override for word {id} names '{written_form}', which is not a new word"
+            ),
+            Self::UnknownOverlayId { kind, id } => {
+                write!(f, "the overlay refers to unknown {kind} {id}")
+            }
+            Self::InvalidOverlay { entry, reason } => {
+                write!(f, "invalid overlay for {entry}: {reason}")
+            }
+            Self::InvalidAnnotation { row, reason } => {
+                write!(f, "invalid annotation row '{row}': {reason}")
+            }
+            Self::UnknownAnnotatedKanji { kanji } => {
+                write!(f, "no skeleton for annotated kanji '{kanji}'")
+            }
+            Self::RepeatedKanjiName { name, kanji, other } => write!(
+                f,
+                "repeated kanji name '{name}' for kanji '{kanji}', already used for '{other}'"
+            ),
+            Self::ConflictingKanjiName {
+                kanji,
+                existing,
+                new,
+            } => write!(
+                f,
+                "the name '{new}' for kanji '{kanji}' conflicts with the existing name '{existing}'"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Collects all the errors encountered during a run.
+#[derive(Debug, Default)]
+pub struct Errors {
+    errors: Vec<Error>,
+}
+
+impl Errors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Adds all the errors collected in `other`.
+    pub fn extend(&mut self, other: Errors) {
+        self.errors.extend(other.errors);
+    }
+
+    /// Fails with all the collected errors, if any.
+    /// If `keep_going` is set, the errors are only logged as skipped entries.
+    pub fn check(self, keep_going: bool) -> eyre::Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else if keep_going {
+            for error in &self.errors {
+                tracing::warn!("skipped entry: {error}");
+            }
+            tracing::warn!("skipped {} entries", self.errors.len());
+            Ok(())
+        } else {
+            Err(eyre::Report::new(self))
+        }
+    }
+}
+
+impl Display for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "encountered {} problems:", self.errors.len())?;
+        for error in &self.errors {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Errors {}
@@ -1,6 +1,25 @@
 //! Types modeling the input files.
 
+pub mod cache;
+pub mod compression;
 pub mod jmdict;
 pub mod jmdict_furigana;
 pub mod kanjidic2;
 pub mod kradfile;
+pub mod table;
+
+use eyre::WrapErr;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// Opens the file for buffered reading, decompressing it if necessary.
+pub fn open(path: &Path) -> eyre::Result<Box<dyn BufRead>> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("Failed to open file at '{}'", path.display()))?;
+    let reader = compression::decompress(BufReader::new(file))
+        .wrap_err_with(|| format!("Failed to read file at '{}'", path.display()))?;
+    Ok(reader)
+}
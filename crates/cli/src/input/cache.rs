@@ -0,0 +1,421 @@
+//! Caches the parsed input files so that they only need to be parsed once.
+//!
+//! The parsed files are stored in the cache directory in the postcard format,
+//! keyed by a hash of the contents of the input file, so a changed input file
+//! is parsed again automatically. Old cache files are never removed,
+//! so the cache directory can be cleared at any time.
+//!
+//! The JMdict entries are cached as they are read, and the header is written to its own file
+//! once all of them have been read, so that the cached header includes the number of entries.
+
+use super::{
+    jmdict::{self, Entries, Entry},
+    open,
+};
+use eyre::WrapErr;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Part of the key of every cache file.
+/// Needs to be changed whenever the models of the input files change so that old cache files are not used.
+const CACHE_VERSION: &str = "2";
+
+/// The size of the scratch buffer used when deserializing cached JMdict entries.
+/// Must be large enough to fit all the strings in a single entry.
+const POSTCARD_SCRATCH_SIZE: usize = 1024 * 1024;
+
+/// A directory of parsed input files.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Reads the JMdict from the cache, or parses it and caches it while it is being read.
+    pub fn jmdict(&self, path: &Path) -> eyre::Result<Jmdict> {
+        let cached = self.cache_path("jmdict", path)?;
+        let cached_header = header_path(&cached);
+        // the header is written last, so the entries are complete if it exists
+        if cached_header.exists() {
+            let mut buf = vec![];
+            File::open(&cached_header)?.read_to_end(&mut buf)?;
+            let header: jmdict::Header = postcard::from_bytes(&buf).wrap_err_with(|| {
+                format!("Failed to read cache file '{}'", cached_header.display())
+            })?;
+            tracing::info!(
+                "using cached JMdict at '{}' with {} entries",
+                cached.display(),
+                header.entry_count.unwrap_or_default()
+            );
+            let reader: Box<dyn BufRead> = Box::new(BufReader::new(File::open(&cached)?));
+            return Ok(Jmdict {
+                header,
+                entry_count: 0,
+                source: Source::Cache {
+                    reader,
+                    scratch: vec![0; POSTCARD_SCRATCH_SIZE],
+                    path: cached,
+                    done: false,
+                },
+            });
+        }
+        let mut jmdict = Jmdict::parse(open(path)?)?;
+        match CacheWriter::new(cached) {
+            Ok(writer) => {
+                if let Source::Xml { cache, .. } = &mut jmdict.source {
+                    *cache = Some(writer);
+                }
+            }
+            Err(err) => tracing::warn!("failed to create cache file for JMdict: {err}"),
+        }
+        Ok(jmdict)
+    }
+
+    /// Reads the parsed file from the cache, or parses it with `parse` and caches it.
+    pub fn load<T, F>(&self, kind: &str, path: &Path, parse: F) -> eyre::Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(Box<dyn BufRead>) -> eyre::Result<T>,
+    {
+        let cached = self.cache_path(kind, path)?;
+        if cached.exists() {
+            tracing::info!("using cached {kind} at '{}'", cached.display());
+            let mut buf = vec![];
+            File::open(&cached)?.read_to_end(&mut buf)?;
+            let value = postcard::from_bytes(&buf)
+                .wrap_err_with(|| format!("Failed to read cache file '{}'", cached.display()))?;
+            return Ok(value);
+        }
+        let value = parse(open(path)?)?;
+        if let Err(err) = write_atomically(&cached, |writer| {
+            postcard::to_io(&value, writer)?;
+            Ok(())
+        }) {
+            tracing::warn!("failed to cache {kind}: {err}");
+        }
+        Ok(value)
+    }
+
+    // the path of the cache file for the given input file
+    fn cache_path(&self, kind: &str, path: &Path) -> eyre::Result<PathBuf> {
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to open file at '{}'", path.display()))?;
+        let hash = hash(CACHE_VERSION, kind, file)
+            .wrap_err_with(|| format!("Failed to read file at '{}'", path.display()))?;
+        Ok(self.dir.join(format!("{kind}-{hash}.postcard")))
+    }
+}
+
+// the key of a cache file, which changes with the cache version, the kind of the file and its contents
+fn hash(version: &str, kind: &str, mut contents: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(version);
+    hasher.update(kind);
+    io::copy(&mut contents, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The entries of a JMdict file, either parsed from the XML or read from the cache.
+pub struct Jmdict {
+    header: jmdict::Header,
+    entry_count: usize,
+    source: Source,
+}
+
+enum Source {
+    Xml {
+        entries: Box<Entries<Box<dyn BufRead>>>,
+        // the entries are written to the cache as they are parsed
+        cache: Option<CacheWriter>,
+    },
+    Cache {
+        reader: Box<dyn BufRead>,
+        scratch: Vec<u8>,
+        path: PathBuf,
+        done: bool,
+    },
+}
+
+impl Jmdict {
+    /// Parses the JMdict XML without caching it.
+    pub fn parse(reader: Box<dyn BufRead>) -> eyre::Result<Self> {
+        let entries = Entries::new(reader)?;
+        Ok(Self {
+            header: entries.header().clone(),
+            entry_count: 0,
+            source: Source::Xml {
+                entries: Box::new(entries),
+                cache: None,
+            },
+        })
+    }
+
+    /// The metadata of the JMdict file.
+    /// The number of entries is only known once all the entries have been read, unless they are read from the cache.
+    pub fn header(&self) -> &jmdict::Header {
+        &self.header
+    }
+
+    /// The number of entries read so far.
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+}
+
+impl Iterator for Jmdict {
+    type Item = eyre::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = match &mut self.source {
+            Source::Xml { entries, cache } => {
+                let next = entries.next();
+                if next.is_none() {
+                    self.header.entry_count = Some(self.entry_count);
+                }
+                if let Some(mut writer) = cache.take() {
+                    let res = match &next {
+                        Some(Ok(entry)) => writer.write(Some(entry)).map(|_| Some(writer)),
+                        // the cache is only complete once all the entries have been read
+                        None => writer.finish(&self.header).map(|_| None),
+                        Some(Err(_)) => Ok(None),
+                    };
+                    match res {
+                        Ok(writer) => *cache = writer,
+                        Err(err) => tracing::warn!("failed to cache JMdict: {err}"),
+                    }
+                }
+                next
+            }
+            Source::Cache {
+                reader,
+                scratch,
+                path,
+                done,
+            } => {
+                if *done {
+                    return None;
+                }
+                match postcard::from_io::<Option<Entry>, _>((reader, &mut scratch[..])) {
+                    Ok((Some(entry), _)) => Some(Ok(entry)),
+                    Ok((None, _)) => {
+                        *done = true;
+                        None
+                    }
+                    Err(err) => {
+                        *done = true;
+                        Some(Err(eyre::Report::new(err).wrap_err(format!(
+                            "Failed to read cache file '{}'",
+                            path.display()
+                        ))))
+                    }
+                }
+            }
+        };
+        if let Some(Ok(_)) = next {
+            self.entry_count += 1;
+        }
+        next
+    }
+}
+
+// writes the JMdict entries to a temporary file that replaces the cache file once all the entries are written,
+// so that an interrupted run never leaves an incomplete cache file behind
+struct CacheWriter {
+    writer: BufWriter<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+    finished: bool,
+}
+
+impl CacheWriter {
+    fn new(path: PathBuf) -> eyre::Result<Self> {
+        let tmp = path.with_extension("tmp");
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(&tmp)?);
+        Ok(Self {
+            writer,
+            tmp,
+            path,
+            finished: false,
+        })
+    }
+
+    // the entries are followed by a None to mark the end
+    fn write(&mut self, entry: Option<&Entry>) -> eyre::Result<()> {
+        postcard::to_io(&entry, &mut self.writer)?;
+        Ok(())
+    }
+
+    // the header is written once all the entries have been read so that it includes the number of entries
+    fn finish(&mut self, header: &jmdict::Header) -> eyre::Result<()> {
+        self.write(None)?;
+        self.writer.flush()?;
+        fs::rename(&self.tmp, &self.path)?;
+        self.finished = true;
+        write_atomically(&header_path(&self.path), |writer| {
+            postcard::to_io(header, writer)?;
+            Ok(())
+        })
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+// the path of the cache file for the header of the cached JMdict entries
+fn header_path(path: &Path) -> PathBuf {
+    path.with_extension("header.postcard")
+}
+
+// writes the file through a temporary file so that an interrupted run never leaves an incomplete file behind
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> eyre::Result<()>,
+) -> eyre::Result<()> {
+    let tmp = path.with_extension("tmp");
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    let res = write(&mut writer)
+        .and_then(|_| Ok(writer.flush()?))
+        .and_then(|_| Ok(fs::rename(&tmp, path)?));
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    const JMDICT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!-- Rev 1.09 -->
+<!ENTITY n "noun (common) (futsuumeishi)">
+]>
+<!-- JMdict created: 2023-12-01 -->
+<JMdict>
+<entry>
+<ent_seq>1000010</ent_seq>
+<k_ele><keb>船</keb></k_ele>
+<r_ele><reb>ふね</reb></r_ele>
+<sense><pos>&n;</pos><gloss>ship</gloss></sense>
+</entry>
+<entry>
+<ent_seq>1000020</ent_seq>
+<r_ele><reb>ああ</reb></r_ele>
+<sense><gloss>ah!</gloss></sense>
+</entry>
+</JMdict>
+"#;
+
+    // a fresh directory for each test, so that the tests can run in parallel
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jadata-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(jmdict: &mut Jmdict) -> Vec<serde_json::Value> {
+        jmdict
+            .map(|entry| serde_json::to_value(entry.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn key_changes_with_version_kind_and_contents() {
+        let key = |version, kind, contents: &str| hash(version, kind, contents.as_bytes()).unwrap();
+        let original = key("2", "kradfile", "船 : 舟");
+        assert_eq!(original, key("2", "kradfile", "船 : 舟"));
+        assert_ne!(original, key("3", "kradfile", "船 : 舟"));
+        assert_ne!(original, key("2", "kanjidic2", "船 : 舟"));
+        assert_ne!(original, key("2", "kradfile", "舟 : 舟"));
+    }
+
+    #[test]
+    fn changed_file_is_parsed_again() {
+        let dir = test_dir("load");
+        let cache = Cache::new(dir.join("cache"));
+        let input = dir.join("input.txt");
+        let parsed = Cell::new(0);
+        let load = || {
+            cache
+                .load("text", &input, |mut reader| {
+                    parsed.set(parsed.get() + 1);
+                    let mut text = String::new();
+                    reader.read_to_string(&mut text)?;
+                    Ok(text)
+                })
+                .unwrap()
+        };
+
+        fs::write(&input, "船").unwrap();
+        assert_eq!(load(), "船");
+        assert_eq!(load(), "船");
+        assert_eq!(parsed.get(), 1);
+
+        fs::write(&input, "舟").unwrap();
+        assert_eq!(load(), "舟");
+        assert_eq!(parsed.get(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn jmdict_entries_round_trip() {
+        let dir = test_dir("jmdict");
+        let cache = Cache::new(dir.join("cache"));
+        let input = dir.join("JMdict_e.xml");
+        fs::write(&input, JMDICT).unwrap();
+
+        let mut parsed = cache.jmdict(&input).unwrap();
+        assert!(matches!(parsed.source, Source::Xml { .. }));
+        let parsed_entries = entries(&mut parsed);
+        assert_eq!(parsed.header().entry_count, Some(2));
+
+        let mut cached = cache.jmdict(&input).unwrap();
+        assert!(matches!(cached.source, Source::Cache { .. }));
+        // the cached header already knows the number of entries
+        assert_eq!(cached.header().entry_count, Some(2));
+        assert_eq!(cached.header().revision, "1.09");
+        assert_eq!(entries(&mut cached), parsed_entries);
+        assert_eq!(cached.entry_count(), 2);
+        assert!(cached.next().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn partially_read_jmdict_is_not_cached() {
+        let dir = test_dir("partial");
+        let cache = Cache::new(dir.join("cache"));
+        let input = dir.join("JMdict_e.xml");
+        fs::write(&input, JMDICT).unwrap();
+
+        let mut parsed = cache.jmdict(&input).unwrap();
+        parsed.next().unwrap().unwrap();
+        drop(parsed);
+        assert_eq!(fs::read_dir(dir.join("cache")).unwrap().count(), 0);
+
+        let parsed = cache.jmdict(&input).unwrap();
+        assert!(matches!(parsed.source, Source::Xml { .. }));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+//! Transparently decompresses input files.
+//! The compression is detected from the magic bytes at the start of the file rather than the file extension,
+//! so the files distributed by the EDRDG such as `JMdict_e_examp.gz` can be used as-is.
+
+use std::io::{self, BufRead, BufReader};
+use xz2::bufread::XzDecoder;
+
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Wraps the reader in a decoder if it starts with the magic bytes of a supported compression format.
+/// Supports gzip, xz and zstd, other input is returned unchanged.
+pub fn decompress<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    if reader.fill_buf()?.starts_with(XZ_MAGIC) {
+        tracing::debug!("detected xz compression");
+        Ok(Box::new(BufReader::new(XzDecoder::new(reader))))
+    } else {
+        jadata::compression::decompress(reader)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::{
+        fs,
+        io::{Read, Write},
+    };
+    use xz2::write::XzEncoder;
+
+    const TEXT: &[u8] = "<JMdict>船</JMdict>".as_bytes();
+
+    fn read(compressed: Vec<u8>) -> Vec<u8> {
+        let mut reader = decompress(io::Cursor::new(compressed)).unwrap();
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn xz_round_trip() {
+        let mut encoder = XzEncoder::new(vec![], 6);
+        encoder.write_all(TEXT).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.starts_with(XZ_MAGIC));
+        assert_eq!(read(compressed), TEXT);
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(TEXT).unwrap();
+        assert_eq!(read(encoder.finish().unwrap()), TEXT);
+    }
+
+    #[test]
+    fn uncompressed_input_is_unchanged() {
+        assert_eq!(read(TEXT.to_vec()), TEXT);
+        assert_eq!(read(vec![]), b"");
+        // too short to be the magic bytes
+        assert_eq!(read(XZ_MAGIC[..3].to_vec()), &XZ_MAGIC[..3]);
+    }
+
+    #[test]
+    fn compression_is_detected_regardless_of_extension() {
+        let dir = std::env::temp_dir().join(format!("jadata-compression-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("JMdict_e.xml");
+        let mut encoder = XzEncoder::new(vec![], 6);
+        encoder.write_all(TEXT).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let mut buf = vec![];
+        crate::input::open(&path)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf, TEXT);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
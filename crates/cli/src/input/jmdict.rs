@@ -1,25 +1,142 @@
 //! Models and parses the JMdict file.
 //! See <https://www.edrdg.org/wiki/index.php/JMdict-EDICT_Dictionary_Project>
 
+use eyre::{ContextCompat, WrapErr};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
 use serde::{Deserialize, Serialize};
-use serde_xml_rs::{Deserializer, EventReader, ParserConfig};
-use std::io::Read;
+use std::{collections::HashMap, io::BufRead};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-pub struct JMdict {
-    #[serde(default)]
-    pub entry: Vec<Entry>,
+/// Parses the entries of a JMdict file one at a time,
+/// so that the whole dictionary never needs to be held in memory.
+pub struct Entries<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    header: Header,
+    entities: HashMap<String, String>,
+    entry_count: usize,
+    done: bool,
 }
 
-impl JMdict {
-    pub fn deserialize<R>(r: R) -> Result<Self, serde_xml_rs::Error>
-    where
-        R: Read,
-    {
-        let config = make_config();
-        let reader = EventReader::new_with_config(r, config);
-        <Self as Deserialize>::deserialize(&mut Deserializer::new(reader))
+/// Metadata about a JMdict file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    /// The revision of the JMdict DTD, such as `1.09`, found in the comments before the entries.
+    pub revision: String,
+    /// The date the file was generated, such as `2023-05-12`, found in the comments before the entries.
+    pub date_of_creation: String,
+    /// The number of entries in the file.
+    /// The file does not record it, so it is only known once all the entries have been parsed,
+    /// or up front when the entries are read from the cache.
+    pub entry_count: Option<usize>,
+}
+
+impl<R: BufRead> Entries<R> {
+    /// Reads the DTD and metadata at the start of the file and prepares to parse the entries.
+    pub fn new(r: R) -> eyre::Result<Self> {
+        let mut reader = Reader::from_reader(r);
+        reader.trim_text(true);
+        let mut buf = Vec::new();
+        let mut revision = None;
+        let mut date_of_creation = None;
+        let mut entities = HashMap::new();
+        loop {
+            buf.clear();
+            match reader
+                .read_event_into(&mut buf)
+                .wrap_err("Failed to parse JMdict")?
+            {
+                Event::DocType(doctype) => {
+                    let doctype = std::str::from_utf8(&doctype)?;
+                    revision = parse_revision(doctype);
+                    entities = parse_entities(doctype)?;
+                }
+                Event::Comment(comment) => {
+                    let comment = std::str::from_utf8(&comment)?;
+                    if let Some(date) = comment.trim().strip_prefix("JMdict created:") {
+                        date_of_creation = Some(date.trim().to_string());
+                    }
+                }
+                Event::Start(start) if start.name().as_ref() == b"JMdict" => break,
+                Event::Start(start) | Event::Empty(start) => {
+                    eyre::bail!("Unexpected element <{}>", name(&start)?)
+                }
+                Event::Eof => eyre::bail!("No <JMdict> element found in JMdict file"),
+                // declarations, processing instructions and so on
+                _ => {}
+            }
+        }
+        let header = Header {
+            revision: revision.wrap_err("No revision found in JMdict file")?,
+            date_of_creation: date_of_creation.wrap_err("No creation date found in JMdict file")?,
+            entry_count: None,
+        };
+        Ok(Self {
+            reader,
+            buf,
+            header,
+            entities,
+            entry_count: 0,
+            done: false,
+        })
+    }
+
+    /// The metadata of the JMdict file.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The entities defined in the DTD of the JMdict file, mapping each entity name to its expansion.
+    pub fn entities(&self) -> &HashMap<String, String> {
+        &self.entities
+    }
+
+    /// The number of entries parsed so far.
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+
+    fn next_entry(&mut self) -> eyre::Result<Option<Entry>> {
+        loop {
+            self.buf.clear();
+            let start = match self.reader.read_event_into(&mut self.buf)? {
+                Event::End(end) if end.name().as_ref() == b"JMdict" => continue,
+                Event::Start(start) => start.into_owned(),
+                Event::Empty(empty) => {
+                    eyre::bail!("Unexpected element <{}>", name(&empty)?)
+                }
+                Event::Text(text) => eyre::bail!("Unexpected text {:?}", text),
+                Event::Eof => {
+                    self.header.entry_count = Some(self.entry_count);
+                    return Ok(None);
+                }
+                // declarations, comments and so on
+                _ => continue,
+            };
+            let element = read_element(&mut self.reader, &self.entities, &start, false)?;
+            if element.name != "entry" {
+                eyre::bail!("Unexpected element <{}>", element.name);
+            }
+            self.entry_count += 1;
+            return Entry::try_from(element).map(Some);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Entries<R> {
+    type Item = eyre::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.next_entry().wrap_err("Failed to parse JMdict");
+        if !matches!(res, Ok(Some(_))) {
+            self.done = true;
+        }
+        res.transpose()
     }
 }
 
@@ -38,7 +155,7 @@ pub struct Entry {
 pub struct KEle {
     pub keb: String,
     #[serde(default)]
-    pub ke_inf: Vec<String>,
+    pub ke_inf: Vec<Entity>,
     #[serde(default)]
     pub ke_pri: Vec<String>,
 }
@@ -51,7 +168,7 @@ pub struct REle {
     #[serde(default)]
     pub re_restr: Vec<String>,
     #[serde(default)]
-    pub re_inf: Vec<String>,
+    pub re_inf: Vec<Entity>,
     #[serde(default)]
     pub re_pri: Vec<String>,
 }
@@ -64,27 +181,36 @@ pub struct Sense {
     #[serde(default)]
     pub stagr: Vec<String>,
     #[serde(default)]
-    pub pos: Vec<String>,
+    pub pos: Vec<Entity>,
     #[serde(default)]
     pub xref: Vec<String>,
     #[serde(default)]
     pub ant: Vec<String>,
     #[serde(default)]
-    pub field: Vec<String>,
+    pub field: Vec<Entity>,
     #[serde(default)]
-    pub misc: Vec<String>,
+    pub misc: Vec<Entity>,
     #[serde(default)]
     pub s_inf: Vec<String>,
     #[serde(default)]
     pub lsource: Vec<Lsource>,
     #[serde(default)]
-    pub dial: Vec<String>,
+    pub dial: Vec<Entity>,
     #[serde(default)]
     pub gloss: Vec<Gloss>,
     #[serde(default)]
     pub example: Vec<Example>,
 }
 
+/// A value that is defined by an entity in the JMdict DTD, such as `&v5k;`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entity {
+    /// The name of the entity, such as `v5k`. These are stable and suitable for matching on.
+    pub code: String,
+    /// The expansion of the entity, such as `Godan verb with 'ku' ending`.
+    pub text: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Lsource {
@@ -121,347 +247,498 @@ pub struct ExSrce {
     pub exsrc_type: String,
 }
 
-fn make_config() -> ParserConfig {
-    ParserConfig::new()
-        .add_entity("bra", "Brazilian")
-        .add_entity("hob", "Hokkaido-ben")
-        .add_entity("ksb", "Kansai-ben")
-        .add_entity("ktb", "Kantou-ben")
-        .add_entity("kyb", "Kyoto-ben")
-        .add_entity("kyu", "Kyuushuu-ben")
-        .add_entity("nab", "Nagano-ben")
-        .add_entity("osb", "Osaka-ben")
-        .add_entity("rkb", "Ryuukyuu-ben")
-        .add_entity("thb", "Touhoku-ben")
-        .add_entity("tsb", "Tosa-ben")
-        .add_entity("tsug", "Tsugaru-ben")
-        // <field> entities
-        .add_entity("agric", "agriculture")
-        .add_entity("anat", "anatomy")
-        .add_entity("archeol", "archeology")
-        .add_entity("archit", "architecture")
-        .add_entity("art", "art, aesthetics")
-        .add_entity("astron", "astronomy")
-        .add_entity("audvid", "audiovisual")
-        .add_entity("aviat", "aviation")
-        .add_entity("baseb", "baseball")
-        .add_entity("biochem", "biochemistry")
-        .add_entity("biol", "biology")
-        .add_entity("bot", "botany")
-        .add_entity("Buddh", "Buddhism")
-        .add_entity("bus", "business")
-        .add_entity("cards", "card games")
-        .add_entity("chem", "chemistry")
-        .add_entity("Christn", "Christianity")
-        .add_entity("cloth", "clothing")
-        .add_entity("comp", "computing")
-        .add_entity("cryst", "crystallography")
-        .add_entity("dent", "dentistry")
-        .add_entity("ecol", "ecology")
-        .add_entity("econ", "economics")
-        .add_entity("elec", "electricity, elec. eng.")
-        .add_entity("electr", "electronics")
-        .add_entity("embryo", "embryology")
-        .add_entity("engr", "engineering")
-        .add_entity("ent", "entomology")
-        .add_entity("film", "film")
-        .add_entity("finc", "finance")
-        .add_entity("fish", "fishing")
-        .add_entity("food", "food, cooking")
-        .add_entity("gardn", "gardening, horticulture")
-        .add_entity("genet", "genetics")
-        .add_entity("geogr", "geography")
-        .add_entity("geol", "geology")
-        .add_entity("geom", "geometry")
-        .add_entity("go", "go (game)")
-        .add_entity("golf", "golf")
-        .add_entity("gramm", "grammar")
-        .add_entity("grmyth", "Greek mythology")
-        .add_entity("hanaf", "hanafuda")
-        .add_entity("horse", "horse racing")
-        .add_entity("kabuki", "kabuki")
-        .add_entity("law", "law")
-        .add_entity("ling", "linguistics")
-        .add_entity("logic", "logic")
-        .add_entity("MA", "martial arts")
-        .add_entity("mahj", "mahjong")
-        .add_entity("manga", "manga")
-        .add_entity("math", "mathematics")
-        .add_entity("mech", "mechanical engineering")
-        .add_entity("med", "medicine")
-        .add_entity("met", "meteorology")
-        .add_entity("mil", "military")
-        .add_entity("mining", "mining")
-        .add_entity("music", "music")
-        .add_entity("noh", "noh")
-        .add_entity("ornith", "ornithology")
-        .add_entity("paleo", "paleontology")
-        .add_entity("pathol", "pathology")
-        .add_entity("pharm", "pharmacology")
-        .add_entity("phil", "philosophy")
-        .add_entity("photo", "photography")
-        .add_entity("physics", "physics")
-        .add_entity("physiol", "physiology")
-        .add_entity("politics", "politics")
-        .add_entity("print", "printing")
-        .add_entity("psy", "psychiatry")
-        .add_entity("psyanal", "psychoanalysis")
-        .add_entity("psych", "psychology")
-        .add_entity("rail", "railway")
-        .add_entity("rommyth", "Roman mythology")
-        .add_entity("Shinto", "Shinto")
-        .add_entity("shogi", "shogi")
-        .add_entity("ski", "skiing")
-        .add_entity("sports", "sports")
-        .add_entity("stat", "statistics")
-        .add_entity("stockm", "stock market")
-        .add_entity("sumo", "sumo")
-        .add_entity("telec", "telecommunications")
-        .add_entity("tradem", "trademark")
-        .add_entity("tv", "television")
-        .add_entity("vidg", "video games")
-        .add_entity("zool", "zoology")
-        // <ke_inf> (kanji info) entities
-        .add_entity("ateji", "ateji (phonetic) reading")
-        .add_entity("ik", "word containing irregular kana usage")
-        .add_entity("iK", "word containing irregular kanji usage")
-        .add_entity("io", "irregular okurigana usage")
-        .add_entity("oK", "word containing out-dated kanji or kanji usage")
-        .add_entity("rK", "rarely-used kanji form")
-        .add_entity("sK", "search-only kanji form")
-        // <misc> (miscellaneous) entities
-        .add_entity("abbr", "abbreviation")
-        .add_entity("arch", "archaic")
-        .add_entity("char", "character")
-        .add_entity("chn", "children's language")
-        .add_entity("col", "colloquial")
-        .add_entity("company", "company name")
-        .add_entity("creat", "creature")
-        .add_entity("dated", "dated term")
-        .add_entity("dei", "deity")
-        .add_entity("derog", "derogatory")
-        .add_entity("doc", "document")
-        .add_entity("euph", "euphemistic")
-        .add_entity("ev", "event")
-        .add_entity("fam", "familiar language")
-        .add_entity("fem", "female term or language")
-        .add_entity("fict", "fiction")
-        .add_entity("form", "formal or literary term")
-        .add_entity("given", "given name or forename, gender not specified")
-        .add_entity("group", "group")
-        .add_entity("hist", "historical term")
-        .add_entity("hon", "honorific or respectful (sonkeigo) language")
-        .add_entity("hum", "humble (kenjougo) language")
-        .add_entity("id", "idiomatic expression")
-        .add_entity("joc", "jocular, humorous term")
-        .add_entity("leg", "legend")
-        .add_entity("m-sl", "manga slang")
-        .add_entity("male", "male term or language")
-        .add_entity("myth", "mythology")
-        .add_entity("net-sl", "Internet slang")
-        .add_entity("obj", "object")
-        .add_entity("obs", "obsolete term")
-        .add_entity("on-mim", "onomatopoeic or mimetic word")
-        .add_entity("organization", "organization name")
-        .add_entity("oth", "other")
-        .add_entity("person", "full name of a particular person")
-        .add_entity("place", "place name")
-        .add_entity("poet", "poetical term")
-        .add_entity("pol", "polite (teineigo) language")
-        .add_entity("product", "product name")
-        .add_entity("proverb", "proverb")
-        .add_entity("quote", "quotation")
-        .add_entity("rare", "rare term")
-        .add_entity("relig", "religion")
-        .add_entity("sens", "sensitive")
-        .add_entity("serv", "service")
-        .add_entity("ship", "ship name")
-        .add_entity("sl", "slang")
-        .add_entity("station", "railway station")
-        .add_entity("surname", "family or surname")
-        .add_entity("uk", "word usually written using kana alone")
-        .add_entity("unclass", "unclassified name")
-        .add_entity("vulg", "vulgar expression or word")
-        .add_entity("work", "work of art, literature, music, etc. name")
-        .add_entity(
-            "X",
-            "rude or X-rated term (not displayed in educational software)",
-        )
-        .add_entity("yoji", "yojijukugo")
-        // <pos> (part-of-speech) entities
-        .add_entity("adj-f", "noun or verb acting prenominally")
-        .add_entity("adj-i", "adjective (keiyoushi)")
-        .add_entity("adj-ix", "adjective (keiyoushi) - yoi/ii class")
-        .add_entity("adj-kari", "'kari' adjective (archaic)")
-        .add_entity("adj-ku", "'ku' adjective (archaic)")
-        .add_entity(
-            "adj-na",
-            "adjectival nouns or quasi-adjectives (keiyodoshi)",
-        )
-        .add_entity("adj-nari", "archaic/formal form of na-adjective")
-        .add_entity(
-            "adj-no",
-            "nouns which may take the genitive case particle 'no'",
-        )
-        .add_entity("adj-pn", "pre-noun adjectival (rentaishi)")
-        .add_entity("adj-shiku", "'shiku' adjective (archaic)")
-        .add_entity("adj-t", "'taru' adjective")
-        .add_entity("adv", "adverb (fukushi)")
-        .add_entity("adv-to", "adverb taking the 'to' particle")
-        .add_entity("aux", "auxiliary")
-        .add_entity("aux-adj", "auxiliary adjective")
-        .add_entity("aux-v", "auxiliary verb")
-        .add_entity("conj", "conjunction")
-        .add_entity("cop", "copula")
-        .add_entity("ctr", "counter")
-        .add_entity("exp", "expressions (phrases, clauses, etc.)")
-        .add_entity("int", "interjection (kandoushi)")
-        .add_entity("n", "noun (common) (futsuumeishi)")
-        .add_entity("n-adv", "adverbial noun (fukushitekimeishi)")
-        .add_entity("n-pr", "proper noun")
-        .add_entity("n-pref", "noun, used as a prefix")
-        .add_entity("n-suf", "noun, used as a suffix")
-        .add_entity("n-t", "noun (temporal) (jisoumeishi)")
-        .add_entity("num", "numeric")
-        .add_entity("pn", "pronoun")
-        .add_entity("pref", "prefix")
-        .add_entity("prt", "particle")
-        .add_entity("suf", "suffix")
-        .add_entity("unc", "unclassified")
-        .add_entity("v-unspec", "verb unspecified")
-        .add_entity("v1", "Ichidan verb")
-        .add_entity("v1-s", "Ichidan verb - kureru special class")
-        .add_entity("v2a-s", "Nidan verb with 'u' ending (archaic)")
-        .add_entity(
-            "v2b-k",
-            "Nidan verb (upper class) with 'bu' ending (archaic)",
-        )
-        .add_entity(
-            "v2b-s",
-            "Nidan verb (lower class) with 'bu' ending (archaic)",
-        )
-        .add_entity(
-            "v2d-k",
-            "Nidan verb (upper class) with 'dzu' ending (archaic)",
-        )
-        .add_entity(
-            "v2d-s",
-            "Nidan verb (lower class) with 'dzu' ending (archaic)",
-        )
-        .add_entity(
-            "v2g-k",
-            "Nidan verb (upper class) with 'gu' ending (archaic)",
-        )
-        .add_entity(
-            "v2g-s",
-            "Nidan verb (lower class) with 'gu' ending (archaic)",
-        )
-        .add_entity(
-            "v2h-k",
-            "Nidan verb (upper class) with 'hu/fu' ending (archaic)",
-        )
-        .add_entity(
-            "v2h-s",
-            "Nidan verb (lower class) with 'hu/fu' ending (archaic)",
-        )
-        .add_entity(
-            "v2k-k",
-            "Nidan verb (upper class) with 'ku' ending (archaic)",
-        )
-        .add_entity(
-            "v2k-s",
-            "Nidan verb (lower class) with 'ku' ending (archaic)",
-        )
-        .add_entity(
-            "v2m-k",
-            "Nidan verb (upper class) with 'mu' ending (archaic)",
-        )
-        .add_entity(
-            "v2m-s",
-            "Nidan verb (lower class) with 'mu' ending (archaic)",
-        )
-        .add_entity(
-            "v2n-s",
-            "Nidan verb (lower class) with 'nu' ending (archaic)",
-        )
-        .add_entity(
-            "v2r-k",
-            "Nidan verb (upper class) with 'ru' ending (archaic)",
-        )
-        .add_entity(
-            "v2r-s",
-            "Nidan verb (lower class) with 'ru' ending (archaic)",
-        )
-        .add_entity(
-            "v2s-s",
-            "Nidan verb (lower class) with 'su' ending (archaic)",
-        )
-        .add_entity(
-            "v2t-k",
-            "Nidan verb (upper class) with 'tsu' ending (archaic)",
-        )
-        .add_entity(
-            "v2t-s",
-            "Nidan verb (lower class) with 'tsu' ending (archaic)",
-        )
-        .add_entity(
-            "v2w-s",
-            "Nidan verb (lower class) with 'u' ending and 'we' conjugation (archaic)",
-        )
-        .add_entity(
-            "v2y-k",
-            "Nidan verb (upper class) with 'yu' ending (archaic)",
-        )
-        .add_entity(
-            "v2y-s",
-            "Nidan verb (lower class) with 'yu' ending (archaic)",
-        )
-        .add_entity(
-            "v2z-s",
-            "Nidan verb (lower class) with 'zu' ending (archaic)",
-        )
-        .add_entity("v4b", "Yodan verb with 'bu' ending (archaic)")
-        .add_entity("v4g", "Yodan verb with 'gu' ending (archaic)")
-        .add_entity("v4h", "Yodan verb with 'hu/fu' ending (archaic)")
-        .add_entity("v4k", "Yodan verb with 'ku' ending (archaic)")
-        .add_entity("v4m", "Yodan verb with 'mu' ending (archaic)")
-        .add_entity("v4n", "Yodan verb with 'nu' ending (archaic)")
-        .add_entity("v4r", "Yodan verb with 'ru' ending (archaic)")
-        .add_entity("v4s", "Yodan verb with 'su' ending (archaic)")
-        .add_entity("v4t", "Yodan verb with 'tsu' ending (archaic)")
-        .add_entity("v5aru", "Godan verb - -aru special class")
-        .add_entity("v5b", "Godan verb with 'bu' ending")
-        .add_entity("v5g", "Godan verb with 'gu' ending")
-        .add_entity("v5k", "Godan verb with 'ku' ending")
-        .add_entity("v5k-s", "Godan verb - Iku/Yuku special class")
-        .add_entity("v5m", "Godan verb with 'mu' ending")
-        .add_entity("v5n", "Godan verb with 'nu' ending")
-        .add_entity("v5r", "Godan verb with 'ru' ending")
-        .add_entity("v5r-i", "Godan verb with 'ru' ending (irregular verb)")
-        .add_entity("v5s", "Godan verb with 'su' ending")
-        .add_entity("v5t", "Godan verb with 'tsu' ending")
-        .add_entity("v5u", "Godan verb with 'u' ending")
-        .add_entity("v5u-s", "Godan verb with 'u' ending (special class)")
-        .add_entity("v5uru", "Godan verb - Uru old class verb (old form of Eru)")
-        .add_entity("vi", "intransitive verb")
-        .add_entity("vk", "Kuru verb - special class")
-        .add_entity("vn", "irregular nu verb")
-        .add_entity("vr", "irregular ru verb, plain form ends with -ri")
-        .add_entity("vs", "noun or participle which takes the aux. verb suru")
-        .add_entity("vs-c", "su verb - precursor to the modern suru")
-        .add_entity("vs-i", "suru verb - included")
-        .add_entity("vs-s", "suru verb - special class")
-        .add_entity("vt", "transitive verb")
-        .add_entity(
-            "vz",
-            "Ichidan verb - zuru verb (alternative form of -jiru verbs)",
-        )
-        // <re_inf> (reading info) entities
-        .add_entity(
-            "gikun",
-            "gikun (meaning as reading) or jukujikun (special kanji reading)",
-        )
-        .add_entity("ik", "word containing irregular kana usage")
-        .add_entity("ok", "out-dated or obsolete kana usage")
-        .add_entity("sk", "search-only kana form")
+// a generic XML element, which an entry is read into before converting it to the actual types
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: Option<String>,
+    // set if the text consisted of a single entity defined in the DTD
+    entity: Option<String>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    // consumes a text-only element
+    fn text(self) -> eyre::Result<String> {
+        if let Some(child) = self.children.first() {
+            eyre::bail!("Unexpected element <{}> in <{}>", child.name, self.name);
+        }
+        Ok(self.text.unwrap_or_default())
+    }
+
+    // consumes an element that only contains an entity
+    fn entity(mut self) -> eyre::Result<Entity> {
+        let code = self
+            .entity
+            .take()
+            .wrap_err_with(|| format!("Expected an entity in <{}>", self.name))?;
+        let text = self.text()?;
+        Ok(Entity { code, text })
+    }
+
+    fn take_attribute(&mut self, key: &str) -> Option<String> {
+        let idx = self.attributes.iter().position(|(k, _)| k == key)?;
+        Some(self.attributes.remove(idx).1)
+    }
+
+    // should be called after taking all the known attributes
+    fn check_attributes(&self) -> eyre::Result<()> {
+        if let Some((key, _)) = self.attributes.first() {
+            eyre::bail!("Unexpected attribute {key} in <{}>", self.name);
+        }
+        Ok(())
+    }
+
+    fn unexpected(&self, child: &Element) -> eyre::Report {
+        eyre::eyre!("Unexpected element <{}> in <{}>", child.name, self.name)
+    }
+}
+
+// finds the revision in the <!-- Rev 1.09 ... --> comment in the DTD
+fn parse_revision(dtd: &str) -> Option<String> {
+    dtd.split("<!--").skip(1).find_map(|comment| {
+        let comment = comment.split("-->").next()?;
+        let revision = comment
+            .trim_start()
+            .strip_prefix("Rev")
+            .filter(|rest| rest.starts_with(char::is_whitespace))?;
+        // the comment may continue with the changelog on the same line
+        revision.split_whitespace().next().map(String::from)
+    })
+}
+
+// parses the <!ENTITY name "expansion"> declarations in the DTD
+fn parse_entities(dtd: &str) -> eyre::Result<HashMap<String, String>> {
+    let mut entities = HashMap::new();
+    let mut rest = dtd;
+    while let Some((_, declaration)) = rest.split_once("<!ENTITY") {
+        let declaration = declaration.trim_start();
+        // parameter entities, declared as <!ENTITY % name "...">, are only used within the DTD itself
+        let (parameter, declaration) = match declaration.strip_prefix('%') {
+            Some(declaration) => (true, declaration.trim_start()),
+            None => (false, declaration),
+        };
+        let (name, declaration) = declaration
+            .split_once(char::is_whitespace)
+            .wrap_err("Invalid entity declaration in JMdict DTD")?;
+        let declaration = declaration.trim_start();
+        let quote = declaration
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .wrap_err_with(|| format!("Invalid declaration for entity {name} in JMdict DTD"))?;
+        let (expansion, declaration) = declaration[1..].split_once(quote).wrap_err_with(|| {
+            format!("Unterminated declaration for entity {name} in JMdict DTD")
+        })?;
+        if !parameter {
+            // the first declaration of an entity is binding
+            entities
+                .entry(name.to_string())
+                .or_insert_with(|| expansion.to_string());
+        }
+        rest = declaration;
+    }
+    Ok(entities)
+}
+
+fn name(start: &BytesStart) -> eyre::Result<String> {
+    Ok(std::str::from_utf8(start.name().as_ref())?.to_string())
+}
+
+// reads the element that was started by `start` along with all of its children
+fn read_element<R: BufRead>(
+    reader: &mut Reader<R>,
+    entities: &HashMap<String, String>,
+    start: &BytesStart,
+    empty: bool,
+) -> eyre::Result<Element> {
+    let resolve = |entity: &str| entities.get(entity).map(String::as_str);
+    let mut attributes = vec![];
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = std::str::from_utf8(attribute.key.as_ref())?;
+        // xml:lang is the only namespaced attribute in JMdict
+        let key = key.strip_prefix("xml:").unwrap_or(key).to_string();
+        let value = attribute
+            .decode_and_unescape_value_with(reader, resolve)?
+            .into_owned();
+        attributes.push((key, value));
+    }
+    let mut element = Element {
+        name: name(start)?,
+        attributes,
+        text: None,
+        entity: None,
+        children: vec![],
+    };
+    if empty {
+        return Ok(element);
+    }
+
+    let mut buf = vec![];
+    loop {
+        buf.clear();
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => {
+                let start = start.into_owned();
+                let child = read_element(reader, entities, &start, false)?;
+                element.children.push(child);
+            }
+            Event::Empty(start) => {
+                let start = start.into_owned();
+                let child = read_element(reader, entities, &start, true)?;
+                element.children.push(child);
+            }
+            Event::Text(text) => {
+                let raw = std::str::from_utf8(&text)?;
+                element.entity = raw
+                    .strip_prefix('&')
+                    .and_then(|raw| raw.strip_suffix(';'))
+                    .filter(|entity| entities.contains_key(*entity))
+                    .map(String::from);
+                let text = text.unescape_with(resolve)?;
+                element.text.get_or_insert_with(String::new).push_str(&text);
+            }
+            Event::End(_) => return Ok(element),
+            Event::Eof => eyre::bail!("Unexpected end of file in <{}>", element.name),
+            // comments and so on
+            _ => {}
+        }
+    }
+}
+
+impl TryFrom<Element> for Entry {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        element.check_attributes()?;
+        let mut ent_seq = None;
+        let mut k_ele = vec![];
+        let mut r_ele = vec![];
+        let mut sense = vec![];
+        for child in std::mem::take(&mut element.children) {
+            match child.name.as_str() {
+                "ent_seq" => ent_seq = Some(child.text()?),
+                "k_ele" => k_ele.push(KEle::try_from(child)?),
+                "r_ele" => r_ele.push(REle::try_from(child)?),
+                "sense" => sense.push(Sense::try_from(child)?),
+                _ => return Err(element.unexpected(&child)),
+            }
+        }
+        let ent_seq = ent_seq.wrap_err("Missing <ent_seq> in <entry>")?;
+        Ok(Self {
+            ent_seq,
+            k_ele,
+            r_ele,
+            sense,
+        })
+    }
+}
+
+impl TryFrom<Element> for KEle {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        element.check_attributes()?;
+        let mut keb = None;
+        let mut ke_inf = vec![];
+        let mut ke_pri = vec![];
+        for child in std::mem::take(&mut element.children) {
+            match child.name.as_str() {
+                "keb" => keb = Some(child.text()?),
+                "ke_inf" => ke_inf.push(child.entity()?),
+                "ke_pri" => ke_pri.push(child.text()?),
+                _ => return Err(element.unexpected(&child)),
+            }
+        }
+        let keb = keb.wrap_err("Missing <keb> in <k_ele>")?;
+        Ok(Self {
+            keb,
+            ke_inf,
+            ke_pri,
+        })
+    }
+}
+
+impl TryFrom<Element> for REle {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        element.check_attributes()?;
+        let mut reb = None;
+        let mut re_nokanji = None;
+        let mut re_restr = vec![];
+        let mut re_inf = vec![];
+        let mut re_pri = vec![];
+        for child in std::mem::take(&mut element.children) {
+            match child.name.as_str() {
+                "reb" => reb = Some(child.text()?),
+                "re_nokanji" => re_nokanji = Some(child.text()?),
+                "re_restr" => re_restr.push(child.text()?),
+                "re_inf" => re_inf.push(child.entity()?),
+                "re_pri" => re_pri.push(child.text()?),
+                _ => return Err(element.unexpected(&child)),
+            }
+        }
+        let reb = reb.wrap_err("Missing <reb> in <r_ele>")?;
+        Ok(Self {
+            reb,
+            re_nokanji,
+            re_restr,
+            re_inf,
+            re_pri,
+        })
+    }
+}
+
+impl TryFrom<Element> for Sense {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        element.check_attributes()?;
+        let mut sense = Sense {
+            stagk: vec![],
+            stagr: vec![],
+            pos: vec![],
+            xref: vec![],
+            ant: vec![],
+            field: vec![],
+            misc: vec![],
+            s_inf: vec![],
+            lsource: vec![],
+            dial: vec![],
+            gloss: vec![],
+            example: vec![],
+        };
+        for child in std::mem::take(&mut element.children) {
+            match child.name.as_str() {
+                "stagk" => sense.stagk.push(child.text()?),
+                "stagr" => sense.stagr.push(child.text()?),
+                "pos" => sense.pos.push(child.entity()?),
+                "xref" => sense.xref.push(child.text()?),
+                "ant" => sense.ant.push(child.text()?),
+                "field" => sense.field.push(child.entity()?),
+                "misc" => sense.misc.push(child.entity()?),
+                "s_inf" => sense.s_inf.push(child.text()?),
+                "lsource" => sense.lsource.push(Lsource::try_from(child)?),
+                "dial" => sense.dial.push(child.entity()?),
+                "gloss" => sense.gloss.push(Gloss::try_from(child)?),
+                "example" => sense.example.push(Example::try_from(child)?),
+                _ => return Err(element.unexpected(&child)),
+            }
+        }
+        Ok(sense)
+    }
+}
+
+impl TryFrom<Element> for Lsource {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        let lang = element.take_attribute("lang");
+        let ls_type = element.take_attribute("ls_type");
+        let ls_wasei = element.take_attribute("ls_wasei");
+        element.check_attributes()?;
+        let value = element.text.take();
+        element.text()?;
+        Ok(Self {
+            value,
+            lang,
+            ls_type,
+            ls_wasei,
+        })
+    }
+}
+
+impl TryFrom<Element> for Gloss {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        let lang = element.take_attribute("lang");
+        let g_gend = element.take_attribute("g_gend");
+        let g_type = element.take_attribute("g_type");
+        element.check_attributes()?;
+        let value = element.text()?;
+        Ok(Self {
+            value,
+            lang,
+            g_gend,
+            g_type,
+        })
+    }
+}
+
+impl TryFrom<Element> for Example {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        element.check_attributes()?;
+        let mut ex_srce = None;
+        let mut ex_text = None;
+        let mut ex_sent = vec![];
+        for child in std::mem::take(&mut element.children) {
+            match child.name.as_str() {
+                "ex_srce" => ex_srce = Some(ExSrce::try_from(child)?),
+                "ex_text" => ex_text = Some(child.text()?),
+                "ex_sent" => ex_sent.push(child.text()?),
+                _ => return Err(element.unexpected(&child)),
+            }
+        }
+        let ex_srce = ex_srce.wrap_err("Missing <ex_srce> in <example>")?;
+        let ex_text = ex_text.wrap_err("Missing <ex_text> in <example>")?;
+        Ok(Self {
+            ex_srce,
+            ex_text,
+            ex_sent,
+        })
+    }
+}
+
+impl TryFrom<Element> for ExSrce {
+    type Error = eyre::Report;
+
+    fn try_from(mut element: Element) -> eyre::Result<Self> {
+        let exsrc_type = element
+            .take_attribute("exsrc_type")
+            .wrap_err("Missing exsrc_type in <ex_srce>")?;
+        element.check_attributes()?;
+        let value = element.text()?;
+        Ok(Self { value, exsrc_type })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const JMDICT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!-- Rev 1.09
+	Added something -->
+<!ELEMENT JMdict (entry*)>
+<!ENTITY n "noun (common) (futsuumeishi)">
+]>
+<!-- JMdict created: 2023-12-01 -->
+<JMdict>
+<entry>
+<ent_seq>1000010</ent_seq>
+<k_ele><keb>船</keb></k_ele>
+<r_ele><reb>ふね</reb></r_ele>
+<sense><pos>&n;</pos><gloss>ship</gloss></sense>
+</entry>
+<entry>
+<ent_seq>1000020</ent_seq>
+<r_ele><reb>ああ</reb></r_ele>
+<sense><gloss>ah!</gloss></sense>
+</entry>
+</JMdict>
+"#;
+
+    #[test]
+    fn header() {
+        let mut entries = Entries::new(JMDICT.as_bytes()).unwrap();
+        let header = entries.header();
+        assert_eq!(header.revision, "1.09");
+        assert_eq!(header.date_of_creation, "2023-12-01");
+        assert_eq!(header.entry_count, None);
+
+        let ent_seqs = entries
+            .by_ref()
+            .map(|e| e.unwrap().ent_seq)
+            .collect::<Vec<_>>();
+        assert_eq!(ent_seqs, ["1000010", "1000020"]);
+        assert_eq!(entries.header().entry_count, Some(2));
+    }
+
+    const ENTITIES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!-- Rev 1.09 -->
+<!ENTITY % common "(#PCDATA)">
+<!ENTITY ateji "ateji (phonetic) reading">
+<!ENTITY ik "word containing irregular kana usage">
+<!ENTITY v5k "Godan verb with 'ku' ending">
+<!ENTITY comp 'computing'>
+<!ENTITY uk "word usually written using kana alone">
+<!ENTITY ksb "Kansai-ben">
+<!ENTITY uk "redefined">
+]>
+<!-- JMdict created: 2023-12-01 -->
+<JMdict>
+<entry>
+<ent_seq>1000030</ent_seq>
+<k_ele><keb>書く</keb><ke_inf>&ateji;</ke_inf></k_ele>
+<r_ele><reb>かく</reb><re_inf>&ik;</re_inf></r_ele>
+<sense>
+<pos>&v5k;</pos>
+<field>&comp;</field>
+<misc>&uk;</misc>
+<dial>&ksb;</dial>
+<gloss xml:lang="eng">to write &amp; draw</gloss>
+</sense>
+</entry>
+</JMdict>
+"#;
+
+    fn entity(code: &str, text: &str) -> Entity {
+        Entity {
+            code: code.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn entities_are_parsed_from_the_dtd() {
+        let dtd = ENTITIES.split("<JMdict>").next().unwrap();
+        let entities = parse_entities(dtd).unwrap();
+        assert_eq!(entities.len(), 6);
+        assert_eq!(entities["ateji"], "ateji (phonetic) reading");
+        assert_eq!(entities["comp"], "computing");
+        // the first declaration is binding
+        assert_eq!(entities["uk"], "word usually written using kana alone");
+        assert!(!entities.contains_key("common"));
+    }
+
+    #[test]
+    fn invalid_entity_declaration_is_rejected() {
+        let error = parse_entities(r#"<!ENTITY uk "unterminated>"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unterminated declaration for entity uk in JMdict DTD"
+        );
+        let error = parse_entities("<!ENTITY uk unquoted>").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid declaration for entity uk in JMdict DTD"
+        );
+    }
+
+    #[test]
+    fn entities_keep_their_code_and_text() {
+        let mut entries = Entries::new(ENTITIES.as_bytes()).unwrap();
+        assert_eq!(entries.entities()["ksb"], "Kansai-ben");
+        let entry = entries.next().unwrap().unwrap();
+        assert!(entries.next().is_none());
+
+        assert_eq!(entry.ent_seq, "1000030");
+        assert_eq!(entry.k_ele[0].keb, "書く");
+        assert_eq!(
+            entry.k_ele[0].ke_inf,
+            [entity("ateji", "ateji (phonetic) reading")]
+        );
+        assert_eq!(
+            entry.r_ele[0].re_inf,
+            [entity("ik", "word containing irregular kana usage")]
+        );
+        let sense = &entry.sense[0];
+        assert_eq!(sense.pos, [entity("v5k", "Godan verb with 'ku' ending")]);
+        assert_eq!(sense.field, [entity("comp", "computing")]);
+        assert_eq!(
+            sense.misc,
+            [entity("uk", "word usually written using kana alone")]
+        );
+        assert_eq!(sense.dial, [entity("ksb", "Kansai-ben")]);
+        // predefined XML entities are only unescaped
+        assert_eq!(sense.gloss[0].value, "to write & draw");
+        assert_eq!(sense.gloss[0].lang.as_deref(), Some("eng"));
+    }
 }
@@ -2,8 +2,10 @@
 //! See <https://www.edrdg.org/krad/kradinf.html>
 
 use encoding_rs::EUC_JP;
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, io::Read};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Kradfile {
     pub kanji_to_components: HashMap<String, Vec<String>>,
 }
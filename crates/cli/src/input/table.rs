@@ -0,0 +1,39 @@
+//! Parses the CSV and TSV files used for manual annotations.
+//! The files have no header row, empty lines are skipped and lines starting with `#` are comments.
+
+use eyre::WrapErr;
+use std::{io::Read, path::Path};
+
+/// Reads the rows of a CSV file, or a TSV file if the path ends in `.tsv`.
+/// Surrounding whitespace and empty cells are left out.
+pub fn read<R: Read>(path: &Path, reader: R) -> eyre::Result<Vec<Vec<String>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter(path))
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record.wrap_err_with(|| format!("Invalid table at '{}'", path.display()))?;
+        let row = record
+            .iter()
+            .filter(|cell| !cell.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if !row.is_empty() {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
+
+/// The delimiter of the table at the path, a tab if the path ends in `.tsv` and a comma otherwise.
+pub fn delimiter(path: &Path) -> u8 {
+    if path.extension().is_some_and(|e| e == "tsv") {
+        b'\t'
+    } else {
+        b','
+    }
+}
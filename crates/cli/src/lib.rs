@@ -1,201 +1,452 @@
 pub mod cli;
+pub mod config;
+pub mod diff;
+pub mod error;
 pub mod input;
 pub mod output;
+pub mod parallel;
+pub mod pipeline;
 
 use self::{
-    cli::Format,
-    input::{jmdict::JMdict, jmdict_furigana, kanjidic2::Kanjidic2, kradfile::Kradfile},
-    output::{kanjifile, kanjifile_skeleton, wordfile, wordfile_skeleton},
-};
-use eyre::{ContextCompat, WrapErr};
-use jadata::{kanjifile::Kanjifile, wordfile::Wordfile};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
-    path::Path,
+    cli::{FileKind, Format},
+    config::{Config, Filters},
+    input::cache::Cache,
+    output::{
+        kanjifile,
+        kanjifile_skeleton::{
+            self, annotate,
+            candidates::{self, CandidateOptions},
+        },
+        wordfile, wordfile_skeleton, OutputOptions,
+    },
+    pipeline::Pipeline,
 };
+use eyre::ContextCompat;
+use jadata::{kanjifile::Kanjifile, validate, wordfile::Wordfile};
+use std::path::{Path, PathBuf};
 
-// todo: share code between create/update
+/// Settings shared by all the commands.
+#[derive(Default)]
+pub struct Context {
+    /// If set, entries that cannot be processed are skipped and listed instead of aborting the run.
+    pub keep_going: bool,
+    /// If set, the parsed input files are cached and reused.
+    pub cache: Option<Cache>,
+    /// Filters for the contents of the generated files.
+    pub filters: Filters,
+    /// The path to the overlay with manual corrections applied to the generated files, if any.
+    pub overlay: Option<PathBuf>,
+}
 
+/// Fills the kanjifile skeleton and writes the kanjifile to `output`.
+/// A report of the kanji names that collided is written to `report` if given.
+#[allow(clippy::too_many_arguments)]
 pub fn create_kanjifile(
+    ctx: &Context,
     version: String,
     kanjidic: &Path,
     kradfile: &Path,
     skeleton: &Path,
     output: &Path,
-    format: Format,
+    options: OutputOptions,
+    report: Option<&Path>,
 ) -> eyre::Result<()> {
-    tracing::info!("opening files");
-    let kd2 = open(kanjidic)?;
-    let kf = open(kradfile)?;
-    let kfs = open(skeleton)?;
-
-    tracing::info!("deserializing files");
-    let kd2: Kanjidic2 = serde_xml_rs::from_reader(BufReader::new(kd2))?;
-    let kf: Kradfile = Kradfile::from(BufReader::new(kf))?;
-    let mut kfs: Kanjifile = serde_json::from_reader(BufReader::new(kfs))?;
-
-    tracing::info!("producing kanjifile");
-    kanjifile::fill_skeleton(&mut kfs, version, kd2, kf);
-
-    tracing::info!("writing output");
-    let kf = File::create(output)?;
-    let mut kf = BufWriter::new(kf);
-    match format {
-        Format::Json => {
-            serde_json::to_writer_pretty(kf, &kfs)?;
-        }
-        Format::Postcard => {
-            let serialized = postcard::to_stdvec(&kfs)?;
-            kf.write_all(&serialized)?;
-        }
+    let mut pipeline = Pipeline::new(ctx);
+    pipeline.phase("reading input files");
+    let kd2 = pipeline.kanjidic2(kanjidic)?;
+    let kf = pipeline.kradfile(kradfile)?;
+    let mut kfs: Kanjifile = pipeline.skeleton(skeleton)?;
+    let overlay = pipeline.overlay(ctx.overlay.as_deref())?;
+
+    let names_report = pipeline.transform("producing kanjifile", |errors| {
+        Ok(kanjifile::fill_skeleton(
+            &mut kfs,
+            version,
+            kd2,
+            kf,
+            &ctx.filters,
+            errors,
+        ))
+    })?;
+    if let Some(overlay) = &overlay {
+        pipeline.transform("applying overlay", |errors| {
+            overlay.apply_kanji(&mut kfs, errors)
+        })?;
+    }
+
+    pipeline.phase("writing output");
+    pipeline.write(&kfs, output, options)?;
+    if let Some(report) = report {
+        pipeline.write_report(&names_report, report)?;
     }
     Ok(())
 }
 
 pub fn create_wordfile(
+    ctx: &Context,
     version: String,
     jmdict: &Path,
     jmdict_furigana: &Path,
     skeleton: &Path,
     output: &Path,
-    format: Format,
+    options: OutputOptions,
 ) -> eyre::Result<()> {
-    tracing::info!("opening files");
-    let jmdict = open(jmdict)?;
-    let furigana = open(jmdict_furigana)?;
-    let wfs = open(skeleton)?;
-
-    tracing::info!("parsing jmdict version");
-    let jmdict_version = parse_jmdict_version(&jmdict)?;
-
-    tracing::info!("deserializing");
-    let jmdict = JMdict::deserialize(jmdict)?;
-    let furigana: Vec<jmdict_furigana::Furigana> =
-        serde_json::from_reader(BufReader::new(furigana))?;
-    let mut wfs: Wordfile = serde_json::from_reader(BufReader::new(wfs))?;
-
-    tracing::info!("producing wordfile");
-    wordfile::fill_skeleton(&mut wfs, version, jmdict, jmdict_version, furigana)?;
-
-    tracing::info!("writing output");
-    let wf = File::create(output)?;
-    let mut wf = BufWriter::new(wf);
-    match format {
-        Format::Json => {
-            serde_json::to_writer_pretty(wf, &wfs)?;
+    let mut pipeline = Pipeline::new(ctx);
+    pipeline.phase("reading input files");
+    let mut jmdict = pipeline.jmdict(jmdict)?;
+    let jmdict_header = jmdict.header().clone();
+    let furigana = pipeline.furigana(jmdict_furigana)?;
+    let mut wfs: Wordfile = pipeline.skeleton(skeleton)?;
+    let overlay = pipeline.overlay(ctx.overlay.as_deref())?;
+
+    pipeline.transform("producing wordfile", |errors| {
+        wordfile::fill_skeleton(
+            &mut wfs,
+            version,
+            &mut jmdict,
+            jmdict_header,
+            furigana,
+            &ctx.filters,
+            errors,
+        )?;
+        tracing::info!("processed {} JMdict entries", jmdict.entry_count());
+        Ok(())
+    })?;
+    if let Some(overlay) = &overlay {
+        pipeline.transform("applying overlay", |errors| {
+            overlay.apply_words(&mut wfs, errors)
+        })?;
+    }
+
+    pipeline.phase("writing output");
+    pipeline.write(&wfs, output, options)
+}
+
+/// Generates all the output files described by `config`.
+/// If `update_skeletons` is set, the skeletons are updated first,
+/// and the reports of the updates are written to `report_dir` if given,
+/// along with the report of the kanji names that collided.
+pub fn build(
+    ctx: &Context,
+    config: &Config,
+    update_skeletons: bool,
+    report_dir: Option<&Path>,
+) -> eyre::Result<()> {
+    let kanjifile_version = config.versions.kanjifile()?;
+    let wordfile_version = config.versions.wordfile()?;
+    let sources = &config.sources;
+    let skeletons = &config.skeletons;
+
+    let mut pipeline = Pipeline::new(ctx);
+    pipeline.phase("reading input files");
+    let kd2 = pipeline.kanjidic2(&sources.kanjidic)?;
+    let kf = pipeline.kradfile(&sources.kradfile)?;
+    let mut jmdict = pipeline.jmdict(&sources.jmdict)?;
+    let jmdict_header = jmdict.header().clone();
+    // JMdict is needed up to three times, so it is kept in memory rather than parsed again
+    let entries = jmdict.by_ref().collect::<eyre::Result<Vec<_>>>()?;
+    tracing::info!("read {} JMdict entries", jmdict.entry_count());
+    let furigana = pipeline.furigana(&sources.furigana)?;
+    let successor_overrides = pipeline.successor_overrides(skeletons.word_successors.as_deref())?;
+    let curated_words = pipeline.curated_words(skeletons.curated_words.as_deref())?;
+    let mut kfs: Kanjifile = pipeline.skeleton(&skeletons.kanjifile)?;
+    let mut wfs: Wordfile = pipeline.skeleton(&skeletons.wordfile)?;
+    let overlay = pipeline.overlay(ctx.overlay.as_deref())?;
+
+    if update_skeletons {
+        let kanjifile_report = pipeline.transform("updating kanjifile skeleton", |_| {
+            kanjifile_skeleton::update(&mut kfs, kd2.clone(), entries.iter().cloned().map(Ok))
+        })?;
+        let wordfile_report = pipeline.transform("updating wordfile skeleton", |errors| {
+            wordfile_skeleton::update(
+                &mut wfs,
+                entries.iter().cloned().map(Ok),
+                jmdict_header.clone(),
+                &successor_overrides,
+                &curated_words,
+                errors,
+            )
+        })?;
+
+        // the skeletons are only written once both updates have succeeded
+        // so that a failed update never leaves one of them behind the other
+        pipeline.phase("writing skeletons");
+        pipeline.write_skeleton(&kfs, &skeletons.kanjifile)?;
+        pipeline.write_skeleton(&wfs, &skeletons.wordfile)?;
+        if let Some(report_dir) = report_dir {
+            for extension in ["json", "md"] {
+                let path = report_dir.join(format!("kanjifile_skeleton_report.{extension}"));
+                pipeline.write_report(&kanjifile_report, &path)?;
+                let path = report_dir.join(format!("wordfile_skeleton_report.{extension}"));
+                pipeline.write_report(&wordfile_report, &path)?;
+            }
         }
-        Format::Postcard => {
-            let serialized = postcard::to_stdvec(&wfs)?;
-            wf.write_all(&serialized)?;
+    }
+
+    let names_report = pipeline.transform("producing kanjifile", |errors| {
+        Ok(kanjifile::fill_skeleton(
+            &mut kfs,
+            kanjifile_version,
+            kd2,
+            kf,
+            &ctx.filters,
+            errors,
+        ))
+    })?;
+
+    pipeline.transform("producing wordfile", |errors| {
+        wordfile::fill_skeleton(
+            &mut wfs,
+            wordfile_version,
+            entries.into_iter().map(Ok),
+            jmdict_header,
+            furigana,
+            &ctx.filters,
+            errors,
+        )
+    })?;
+
+    if let Some(overlay) = &overlay {
+        pipeline.transform("applying overlay", |errors| {
+            overlay.apply_kanji(&mut kfs, errors)?;
+            overlay.apply_words(&mut wfs, errors)
+        })?;
+    }
+
+    pipeline.phase("writing output");
+    for options in config.output.all_options() {
+        let kanjifile = config.output.dir.join(options.file_name("kanjifile"));
+        pipeline.write(&kfs, &kanjifile, options)?;
+        let wordfile = config.output.dir.join(options.file_name("wordfile"));
+        pipeline.write(&wfs, &wordfile, options)?;
+    }
+    if let Some(report_dir) = report_dir {
+        for extension in ["json", "md"] {
+            let path = report_dir.join(format!("kanji_names_report.{extension}"));
+            pipeline.write_report(&names_report, &path)?;
         }
     }
     Ok(())
 }
 
-pub fn create_kanjifile_skeleton(
+/// Updates the kanjifile skeleton at `output`, or creates a new one if `clean` is set.
+/// A report of the changes is written to `report` if given.
+pub fn generate_kanjifile_skeleton(
+    ctx: &Context,
     kanjidic: &Path,
     jmdict: &Path,
     output: &Path,
+    report: Option<&Path>,
+    clean: bool,
 ) -> eyre::Result<()> {
-    tracing::info!("opening files");
-    let kd2 = open(kanjidic)?;
-    let jmdict = open(jmdict)?;
-
-    tracing::info!("deserializing files");
-    let kd2: Kanjidic2 = serde_xml_rs::from_reader(BufReader::new(kd2))?;
-    let jmdict = JMdict::deserialize(jmdict)?;
+    let mut pipeline = Pipeline::new(ctx);
+    pipeline.phase("reading input files");
+    let kd2 = pipeline.kanjidic2(kanjidic)?;
+    let jmdict = pipeline.jmdict(jmdict)?;
 
-    tracing::info!("producing kanjifile skeleton");
-    let skeleton = kanjifile_skeleton::create(kd2, jmdict)?;
+    let (skeleton, changes) = if clean {
+        pipeline.transform("producing kanjifile skeleton", |_| {
+            kanjifile_skeleton::create(kd2, jmdict)
+        })?
+    } else {
+        let mut skeleton: Kanjifile = pipeline.skeleton(output)?;
+        let changes = pipeline.transform("updating kanjifile skeleton", |_| {
+            kanjifile_skeleton::update(&mut skeleton, kd2, jmdict)
+        })?;
+        (skeleton, changes)
+    };
 
-    tracing::info!("writing output");
-    let output = File::create(output)?;
-    serde_json::to_writer_pretty(BufWriter::new(output), &skeleton)?;
+    pipeline.phase("writing output");
+    pipeline.write_skeleton(&skeleton, output)?;
+    if let Some(report) = report {
+        pipeline.write_report(&changes, report)?;
+    }
     Ok(())
 }
 
-pub fn update_kanjifile_skeleton(
-    kanjidic: &Path,
+/// Updates the wordfile skeleton at `output`, or creates a new one if `clean` is set.
+/// Words removed from the skeleton may be carried forward according to the overrides in `successors`.
+/// The words in `curated_words` are added to the skeleton, or replace the curated words already in it.
+/// A report of the changes is written to `report` if given.
+pub fn generate_wordfile_skeleton(
+    ctx: &Context,
     jmdict: &Path,
+    successors: Option<&Path>,
+    curated_words: Option<&Path>,
     output: &Path,
+    report: Option<&Path>,
+    clean: bool,
 ) -> eyre::Result<()> {
-    tracing::info!("opening files");
-    let kd2 = open(kanjidic)?;
-    let jmdict = open(jmdict)?;
-    let kf = open(output)?;
-
-    tracing::info!("deserializing files");
-    let kd2: Kanjidic2 = serde_xml_rs::from_reader(BufReader::new(kd2))?;
-    let jmdict = JMdict::deserialize(jmdict)?;
-    let mut kf: Kanjifile = serde_json::from_reader(kf)?;
-
-    tracing::info!("updating kanjifile skeleton");
-    kanjifile_skeleton::update(&mut kf, kd2, jmdict)?;
-
-    tracing::info!("writing output");
-    let output = File::create(output)?;
-    serde_json::to_writer_pretty(BufWriter::new(output), &kf)?;
-    Ok(())
-}
+    let mut pipeline = Pipeline::new(ctx);
+    pipeline.phase("reading input files");
+    let mut jmdict = pipeline.jmdict(jmdict)?;
+    let jmdict_header = jmdict.header().clone();
+    let successor_overrides = pipeline.successor_overrides(successors)?;
+    let curated_words = pipeline.curated_words(curated_words)?;
 
-pub fn create_wordfile_skeleton(jmdict: &Path, output: &Path) -> eyre::Result<()> {
-    tracing::info!("opening files");
-    let jmdict = open(jmdict)?;
+    let (skeleton, changes) = if clean {
+        pipeline.transform("producing wordfile skeleton", |errors| {
+            wordfile_skeleton::create(&mut jmdict, jmdict_header, &curated_words, errors)
+        })?
+    } else {
+        let mut skeleton: Wordfile = pipeline.skeleton(output)?;
+        let changes = pipeline.transform("updating wordfile skeleton", |errors| {
+            wordfile_skeleton::update(
+                &mut skeleton,
+                &mut jmdict,
+                jmdict_header,
+                &successor_overrides,
+                &curated_words,
+                errors,
+            )
+        })?;
+        (skeleton, changes)
+    };
+    tracing::info!("processed {} JMdict entries", jmdict.entry_count());
 
-    tracing::info!("parsing jmdict version");
-    let version = parse_jmdict_version(&jmdict)?;
+    pipeline.phase("writing output");
+    pipeline.write_skeleton(&skeleton, output)?;
+    if let Some(report) = report {
+        pipeline.write_report(&changes, report)?;
+    }
+    Ok(())
+}
 
-    tracing::info!("deserializing");
-    let jmdict = JMdict::deserialize(BufReader::new(jmdict))?;
+/// Imports the kanji names in `names` and the similar kanji in `similar` into the kanjifile skeleton,
+/// keeping the similar kanji symmetric. Names that conflict with existing ones are replaced only if `overwrite` is set.
+pub fn annotate_kanjifile_skeleton(
+    ctx: &Context,
+    skeleton: &Path,
+    names: Option<&Path>,
+    similar: Option<&Path>,
+    overwrite: bool,
+) -> eyre::Result<()> {
+    let mut pipeline = Pipeline::new(ctx);
+    pipeline.phase("reading input files");
+    let mut kfs: Kanjifile = pipeline.skeleton(skeleton)?;
+    let names = names.map(|path| pipeline.table(path)).transpose()?;
+    let similar = similar.map(|path| pipeline.table(path)).transpose()?;
 
-    tracing::info!("producing wordfile skeleton");
-    let skeleton = wordfile_skeleton::create(jmdict, version)?;
+    pipeline.transform("annotating kanjifile skeleton", |errors| {
+        if let Some(names) = &names {
+            annotate::names(&mut kfs, names, overwrite, errors);
+        }
+        if let Some(similar) = &similar {
+            annotate::similar(&mut kfs, similar, errors);
+        }
+        annotate::make_similar_symmetric(&mut kfs, errors);
+        Ok(())
+    })?;
 
-    tracing::info!("writing output");
-    let output = File::create(output)?;
-    serde_json::to_writer_pretty(BufWriter::new(output), &skeleton)?;
-    Ok(())
+    pipeline.phase("writing output");
+    pipeline.write_skeleton(&kfs, skeleton)
 }
 
-pub fn update_wordfile_skeleton(jmdict: &Path, output: &Path) -> eyre::Result<()> {
-    tracing::info!("opening files");
-    let jmdict = open(jmdict)?;
-    let wf = open(output)?;
-
-    tracing::info!("parsing jmdict version");
-    let version = parse_jmdict_version(&jmdict)?;
+/// Writes the candidates for similar kanji in the kanjifile skeleton to a review table at `output`.
+pub fn propose_similar_kanji(
+    ctx: &Context,
+    kanjidic: &Path,
+    kradfile: &Path,
+    skeleton: &Path,
+    output: &Path,
+    options: CandidateOptions,
+) -> eyre::Result<()> {
+    let mut pipeline = Pipeline::new(ctx);
+    pipeline.phase("reading input files");
+    let kd2 = pipeline.kanjidic2(kanjidic)?;
+    let kf = pipeline.kradfile(kradfile)?;
+    let kfs: Kanjifile = pipeline.skeleton(skeleton)?;
 
-    tracing::info!("deserializing");
-    let jmdict = JMdict::deserialize(BufReader::new(jmdict))?;
-    let mut wf: Wordfile = serde_json::from_reader(wf)?;
+    let candidates = pipeline.transform("finding similar kanji", |_| {
+        Ok(candidates::find(&kfs, &kd2, &kf, options))
+    })?;
+    tracing::info!("found {} candidates", candidates.len());
 
-    tracing::info!("updating wordfile skeleton");
-    wordfile_skeleton::update(&mut wf, jmdict, version)?;
+    pipeline.phase("writing output");
+    tracing::info!("writing {}", output.display());
+    candidates::write(&candidates, output)
+}
 
-    tracing::info!("writing output");
-    let output = File::create(output)?;
-    serde_json::to_writer_pretty(BufWriter::new(output), &wf)?;
+/// Checks the invariants of the given files and prints the problems found.
+/// The format of each file is detected from its extension unless given.
+pub fn validate(
+    kanjifile: Option<&Path>,
+    wordfile: Option<&Path>,
+    kanjifile_format: Option<Format>,
+    wordfile_format: Option<Format>,
+) -> eyre::Result<()> {
+    let ctx = Context::default();
+    let mut pipeline = Pipeline::new(&ctx);
+    let mut violation_count = 0;
+    if let Some(kanjifile) = kanjifile {
+        pipeline.phase("validating kanjifile");
+        let format = format_of(kanjifile, kanjifile_format, "--kanjifile-format")?;
+        let kf: Kanjifile = pipeline.read(kanjifile, format)?;
+        let violations = validate::kanjifile(&kf);
+        for violation in &violations {
+            println!("{}: {violation}", kanjifile.display());
+        }
+        violation_count += violations.len();
+    }
+    if let Some(wordfile) = wordfile {
+        pipeline.phase("validating wordfile");
+        let format = format_of(wordfile, wordfile_format, "--wordfile-format")?;
+        let wf: Wordfile = pipeline.read(wordfile, format)?;
+        let violations = validate::wordfile(&wf);
+        for violation in &violations {
+            println!("{}: {violation}", wordfile.display());
+        }
+        violation_count += violations.len();
+    }
+    if violation_count > 0 {
+        eyre::bail!("Found {violation_count} problems");
+    }
     Ok(())
 }
 
-fn open(path: &Path) -> eyre::Result<File> {
-    File::open(path).wrap_err_with(|| format!("Failed to open file at '{}'", path.display()))
+/// Prints the changes between the `old` and `new` versions of a generated file, as JSON if `json` is set.
+/// The format of each file is detected from its extension unless given.
+pub fn diff(
+    kind: FileKind,
+    old: &Path,
+    new: &Path,
+    old_format: Option<Format>,
+    new_format: Option<Format>,
+    json: bool,
+) -> eyre::Result<()> {
+    let old_format = format_of(old, old_format, "--old-format")?;
+    let new_format = format_of(new, new_format, "--new-format")?;
+    let ctx = Context::default();
+    let mut pipeline = Pipeline::new(&ctx);
+    pipeline.phase("reading input files");
+    let diff = match kind {
+        FileKind::Kanjifile => {
+            let old: Kanjifile = pipeline.read(old, old_format)?;
+            let new: Kanjifile = pipeline.read(new, new_format)?;
+            pipeline.phase("comparing kanjifiles");
+            diff::kanjifiles(&old, &new)?
+        }
+        FileKind::Wordfile => {
+            let old: Wordfile = pipeline.read(old, old_format)?;
+            let new: Wordfile = pipeline.read(new, new_format)?;
+            pipeline.phase("comparing wordfiles");
+            diff::wordfiles(&old, &new)?
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        println!("{diff}");
+    }
+    Ok(())
 }
 
-fn parse_jmdict_version(jmdict: &File) -> eyre::Result<String> {
-    let lines = BufReader::new(jmdict.try_clone()?).lines();
-    let mut jmdict = BufReader::new(jmdict);
-    let version = lines
-        .take(10) // the rev should be in the first couple lines
-        .find_map(|l| match l {
-            Ok(l) => {
-                let (_, rev) = l.split_once("<!-- Rev ")?;
-                Some(Ok(rev.to_string()))
-            }
-            Err(err) => Some(Err(err)),
-        })
-        .wrap_err("No revision found in jmdict file")??;
-    jmdict.seek(SeekFrom::Start(0))?;
-    Ok(version)
+// the format given on the command line, or the one detected from the extension of the file
+fn format_of(path: &Path, format: Option<Format>, flag: &str) -> eyre::Result<Format> {
+    format.or_else(|| Format::detect(path)).wrap_err_with(|| {
+        format!(
+            "Cannot detect the format of '{}' from its extension, give it with --format or {flag}",
+            path.display()
+        )
+    })
 }
@@ -1,11 +1,48 @@
 //! Creates the kanjifile and wordfile files.
 
 use clap::Parser;
-use jadata_cli::cli::{Cli, Command};
+use jadata_cli::{
+    cli::{Cli, Command, ConfigCommand},
+    config::{self, Config},
+    input::cache::Cache,
+    output::kanjifile_skeleton::candidates::CandidateOptions,
+    Context,
+};
+use std::path::Path;
+use tracing_subscriber::fmt::format::FmtSpan;
 
 fn main() -> eyre::Result<()> {
-    tracing_subscriber::fmt::init();
-    let cli = Cli::parse();
+    // logging the closing of the spans reports the time spent in each phase
+    // the log is written to stderr so that it does not mix with the output of `diff` and `validate`
+    tracing_subscriber::fmt()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .init();
+    let mut cli = Cli::parse();
+    if let Command::Config { command } = cli.command {
+        match command {
+            ConfigCommand::Init { force } => {
+                let path = cli
+                    .config
+                    .as_deref()
+                    .unwrap_or(Path::new(config::DEFAULT_PATH));
+                Config::init(path, force)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut config = Config::load(cli.config.as_deref())?;
+    config.override_with_globals(&mut cli);
+    let ctx = Context {
+        keep_going: cli.keep_going,
+        cache: config
+            .cache
+            .enabled
+            .then(|| Cache::new(config.cache.dir.clone())),
+        filters: config.filters.clone(),
+        overlay: config.skeletons.overlay.clone(),
+    };
 
     match cli.command {
         Command::Kanjifile {
@@ -14,10 +51,25 @@ fn main() -> eyre::Result<()> {
             kradfile,
             skeleton,
             output,
-            format,
+            options,
+            report,
         } => {
+            let version = match version {
+                Some(version) => version,
+                None => config.versions.kanjifile()?,
+            };
+            let options = config.output.options(options)?;
+            let output =
+                output.unwrap_or_else(|| config.output.dir.join(options.file_name("kanjifile")));
             jadata_cli::create_kanjifile(
-                version, &kanjidic, &kradfile, &skeleton, &output, format,
+                &ctx,
+                version,
+                &kanjidic.unwrap_or_else(|| config.sources.kanjidic.clone()),
+                &kradfile.unwrap_or_else(|| config.sources.kradfile.clone()),
+                &skeleton.unwrap_or_else(|| config.skeletons.kanjifile.clone()),
+                &output,
+                options,
+                report.as_deref(),
             )?;
         }
         Command::Wordfile {
@@ -26,33 +78,143 @@ fn main() -> eyre::Result<()> {
             furigana,
             skeleton,
             output,
-            format,
+            options,
         } => {
-            jadata_cli::create_wordfile(version, &jmdict, &furigana, &skeleton, &output, format)?;
+            let version = match version {
+                Some(version) => version,
+                None => config.versions.wordfile()?,
+            };
+            let options = config.output.options(options)?;
+            let output =
+                output.unwrap_or_else(|| config.output.dir.join(options.file_name("wordfile")));
+            jadata_cli::create_wordfile(
+                &ctx,
+                version,
+                &jmdict.unwrap_or_else(|| config.sources.jmdict.clone()),
+                &furigana.unwrap_or_else(|| config.sources.furigana.clone()),
+                &skeleton.unwrap_or_else(|| config.skeletons.wordfile.clone()),
+                &output,
+                options,
+            )?;
         }
         Command::KanjifileSkeleton {
             clean,
             kanjidic,
             jmdict,
             output,
+            report,
         } => {
-            if !clean {
-                jadata_cli::update_kanjifile_skeleton(&kanjidic, &jmdict, &output)?;
-            } else {
-                jadata_cli::create_kanjifile_skeleton(&kanjidic, &jmdict, &output)?;
-            }
+            let kanjidic = kanjidic.unwrap_or_else(|| config.sources.kanjidic.clone());
+            let jmdict = jmdict.unwrap_or_else(|| config.sources.jmdict.clone());
+            let output = output.unwrap_or_else(|| config.skeletons.kanjifile.clone());
+            jadata_cli::generate_kanjifile_skeleton(
+                &ctx,
+                &kanjidic,
+                &jmdict,
+                &output,
+                report.as_deref(),
+                clean,
+            )?;
         }
         Command::WordfileSkeleton {
             clean,
             jmdict,
+            successors,
+            curated_words,
             output,
+            report,
         } => {
-            if !clean {
-                jadata_cli::update_wordfile_skeleton(&jmdict, &output)?;
-            } else {
-                jadata_cli::create_wordfile_skeleton(&jmdict, &output)?;
-            }
+            let jmdict = jmdict.unwrap_or_else(|| config.sources.jmdict.clone());
+            let successors = successors.or_else(|| config.skeletons.word_successors.clone());
+            let curated_words = curated_words.or_else(|| config.skeletons.curated_words.clone());
+            let output = output.unwrap_or_else(|| config.skeletons.wordfile.clone());
+            jadata_cli::generate_wordfile_skeleton(
+                &ctx,
+                &jmdict,
+                successors.as_deref(),
+                curated_words.as_deref(),
+                &output,
+                report.as_deref(),
+                clean,
+            )?;
+        }
+        Command::KanjiAnnotate {
+            names,
+            similar,
+            overwrite,
+            skeleton,
+        } => {
+            let skeleton = skeleton.unwrap_or_else(|| config.skeletons.kanjifile.clone());
+            jadata_cli::annotate_kanjifile_skeleton(
+                &ctx,
+                &skeleton,
+                names.as_deref(),
+                similar.as_deref(),
+                overwrite,
+            )?;
+        }
+        Command::SimilarCandidates {
+            kanjidic,
+            kradfile,
+            skeleton,
+            output,
+            min_overlap,
+            max_stroke_difference,
+            per_kanji,
+        } => {
+            let options = CandidateOptions {
+                min_overlap,
+                max_stroke_difference,
+                per_kanji,
+            };
+            jadata_cli::propose_similar_kanji(
+                &ctx,
+                &kanjidic.unwrap_or_else(|| config.sources.kanjidic.clone()),
+                &kradfile.unwrap_or_else(|| config.sources.kradfile.clone()),
+                &skeleton.unwrap_or_else(|| config.skeletons.kanjifile.clone()),
+                &output,
+                options,
+            )?;
+        }
+        Command::Build(args) => {
+            let update_skeletons = args.update_skeletons;
+            let report_dir = args.report_dir.clone();
+            config.override_with(args);
+            jadata_cli::build(&ctx, &config, update_skeletons, report_dir.as_deref())?;
+        }
+        Command::Validate {
+            kanjifile,
+            wordfile,
+            format,
+            kanjifile_format,
+            wordfile_format,
+        } => {
+            jadata_cli::validate(
+                kanjifile.as_deref(),
+                wordfile.as_deref(),
+                kanjifile_format.or(format),
+                wordfile_format.or(format),
+            )?;
+        }
+        Command::Diff {
+            file,
+            old,
+            new,
+            format,
+            old_format,
+            new_format,
+            json,
+        } => {
+            jadata_cli::diff(
+                file,
+                &old,
+                &new,
+                old_format.or(format),
+                new_format.or(format),
+                json,
+            )?;
         }
+        Command::Config { .. } => unreachable!("handled above"),
     }
 
     Ok(())
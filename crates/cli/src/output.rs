@@ -1,6 +1,35 @@
 //! Types modeling the output files.
 
+pub mod compression;
 pub mod kanjifile;
 pub mod kanjifile_skeleton;
+pub mod overlay;
+pub mod report;
+pub mod sink;
 pub mod wordfile;
 pub mod wordfile_skeleton;
+
+use crate::cli::{Compression, Format};
+
+/// Options for writing the resulting file.
+#[derive(Clone, Copy)]
+pub struct OutputOptions {
+    /// The format of the output file.
+    pub format: Format,
+    /// The compression to apply to the output file, if any.
+    pub compression: Option<Compression>,
+    /// If set, JSON output is written without any whitespace. Has no effect on other formats.
+    pub compact: bool,
+}
+
+impl OutputOptions {
+    /// The name of an output file with these options, such as "wordfile.json.gz" for the name "wordfile".
+    pub fn file_name(&self, name: &str) -> String {
+        let mut file_name = format!("{name}.{}", self.format.extension());
+        if let Some(compression) = self.compression {
+            file_name.push('.');
+            file_name.push_str(compression.extension());
+        }
+        file_name
+    }
+}
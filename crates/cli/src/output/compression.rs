@@ -0,0 +1,100 @@
+//! Compresses output files.
+
+use crate::cli::Compression;
+use flate2::write::GzEncoder;
+use std::io::{self, Write};
+
+/// Compresses everything written to it with the chosen compression, if any.
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W, compression: Option<Compression>) -> io::Result<Self> {
+        let encoder = match compression {
+            None => Self::None(writer),
+            Some(Compression::Gzip) => {
+                Self::Gzip(GzEncoder::new(writer, flate2::Compression::best()))
+            }
+            Some(Compression::Zstd) => Self::Zstd(zstd::Encoder::new(writer, 19)?),
+        };
+        Ok(encoder)
+    }
+
+    /// Writes the end of the compressed stream and returns the underlying writer.
+    /// Needs to be called, as dropping the encoder may silently leave the output incomplete.
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            Self::None(writer) => writer,
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::None(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::None(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        cli::Format,
+        output::{sink, OutputOptions},
+    };
+    use jadata::{
+        id::WordId,
+        wordfile::{Header, Wordfile},
+    };
+    use std::fs;
+
+    #[test]
+    fn compressed_files_are_read_back() {
+        let wordfile = Wordfile {
+            header: Header {
+                version: "2".to_string(),
+                jmdict_version: "1.09".to_string(),
+                jmdict_date_of_creation: "2023-12-01".to_string(),
+                last_word_id: WordId(0),
+                tombstones: vec![],
+            },
+            words: vec![],
+        };
+        let dir = std::env::temp_dir().join(format!("jadata-encoder-{}", std::process::id()));
+        for format in [Format::Json, Format::Postcard] {
+            for compression in [None, Some(Compression::Gzip), Some(Compression::Zstd)] {
+                let options = OutputOptions {
+                    format,
+                    compression,
+                    compact: false,
+                };
+                let path = dir.join(options.file_name("wordfile"));
+                sink::write(&wordfile, &path, options).unwrap();
+                let gzipped = fs::read(&path).unwrap().starts_with(&[0x1f, 0x8b]);
+                assert_eq!(gzipped, matches!(compression, Some(Compression::Gzip)));
+                let read: Wordfile = sink::read(&path, format).unwrap();
+                assert_eq!(read.header.version, "2", "{}", path.display());
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,12 +1,32 @@
-use crate::input::{
-    kanjidic2::{self, Character, Kanjidic2},
-    kradfile::Kradfile,
+use crate::{
+    config::Filters,
+    error::{Error, Errors},
+    input::{
+        kanjidic2::{self, Character, Kanjidic2},
+        kradfile::Kradfile,
+    },
+    output::report::{KanjiNamesReport, NameCollision},
+};
+use jadata::{
+    id::KanjiId,
+    kanjifile::{Kanji, Kanjifile, NameSource},
 };
-use jadata::kanjifile::{Kanji, Kanjifile};
 use std::collections::{HashMap, HashSet};
 
 /// Fills the kanjifile skeleton with data.
-pub fn fill_skeleton(skeleton: &mut Kanjifile, version: String, kd2: Kanjidic2, kf: Kradfile) {
+/// Kanji that cannot be filled in are skipped and recorded in `errors`.
+///
+/// The names in the skeleton are curated and kept as they are.
+/// The other kanji are named after the first of their meanings, in KANJIDIC2 order,
+/// that is not already the name of another kanji, see [`derive_names`].
+pub fn fill_skeleton(
+    skeleton: &mut Kanjifile,
+    version: String,
+    kd2: Kanjidic2,
+    kf: Kradfile,
+    filters: &Filters,
+    errors: &mut Errors,
+) -> KanjiNamesReport {
     let mut skeleton_map = skeleton
         .kanji
         .iter_mut()
@@ -15,39 +35,68 @@ pub fn fill_skeleton(skeleton: &mut Kanjifile, version: String, kd2: Kanjidic2,
 
     let mut seen_ids = HashSet::new();
     let mut seen_kanji = HashSet::new();
+    let mut frequencies = HashMap::new();
     for kanji in kd2.character {
-        let kanji_skeleton = skeleton_map
-            .get_mut(&kanji.literal)
-            .map(|k| &mut **k)
-            .unwrap_or_else(|| panic!("no skeleton for {}", kanji.literal));
+        let kanji_skeleton = match skeleton_map.get_mut(&kanji.literal) {
+            Some(kanji_skeleton) => &mut **kanji_skeleton,
+            None => {
+                errors.push(Error::MissingKanjiSkeleton {
+                    kanji: kanji.literal,
+                });
+                continue;
+            }
+        };
 
         if kanji.literal.chars().count() != 1 {
-            panic!("multi-codepoint literal {}", kanji.literal);
+            errors.push(Error::MultiCodepointLiteral {
+                literal: kanji.literal,
+            });
+            continue;
         }
         if !seen_kanji.insert(kanji.literal.clone()) {
-            panic!("repeated kanji {}", kanji.literal);
+            errors.push(Error::RepeatedKanji {
+                kanji: kanji.literal,
+            });
+            continue;
         }
         if !seen_ids.insert(kanji_skeleton.id) {
-            panic!("repeated id {}", kanji_skeleton.id);
+            errors.push(Error::RepeatedKanjiId {
+                id: kanji_skeleton.id,
+                kanji: kanji.literal,
+            });
+            continue;
+        }
+        if let Some(freq) = kanji
+            .misc
+            .freq
+            .as_deref()
+            .and_then(|f| f.parse::<u32>().ok())
+        {
+            frequencies.insert(kanji_skeleton.id, freq);
         }
-        fill_in_kanji(kanji, kanji_skeleton, &kf.kanji_to_components);
+        fill_in_kanji(kanji, kanji_skeleton, &kf.kanji_to_components, filters);
     }
+    let collisions = derive_names(skeleton, &frequencies, errors);
     skeleton.header.version = version;
     skeleton.header.kanjidic2_version = kd2.header.file_version;
+    skeleton.header.kanjidic2_date_of_creation = kd2.header.date_of_creation;
+    KanjiNamesReport {
+        kanjidic2_version: skeleton.header.kanjidic2_version.clone(),
+        collisions,
+    }
 }
 
 fn fill_in_kanji(
     kanji: Character,
     skeleton: &mut Kanji,
     kanji_to_components: &HashMap<String, Vec<String>>,
+    filters: &Filters,
 ) {
     let mut meanings = vec![];
     for rmg in kanji.reading_meaning.into_iter().flat_map(|rm| rm.rmgroup) {
-        meanings.extend(handle_meanings(rmg.meaning));
+        meanings.extend(handle_meanings(rmg.meaning, filters));
     }
-    meanings.sort();
 
-    skeleton.name = skeleton.name.take().or_else(|| meanings.first().cloned());
     skeleton.components = kanji_to_components
         .get(&kanji.literal)
         .cloned()
@@ -55,9 +104,197 @@ fn fill_in_kanji(
     skeleton.meanings = meanings;
 }
 
-fn handle_meanings(meanings: Vec<kanjidic2::Meaning>) -> impl Iterator<Item = String> {
+/// Names the kanji that do not have a curated name.
+/// Each name is unique, so the kanji are named in order of their KANJIDIC2 frequency rank,
+/// with the most common kanji first, and a kanji whose first meaning is already taken
+/// falls back to its next meaning. Kanji with no free meaning are left unnamed.
+/// Curated names that are used for multiple kanji are recorded in `errors`,
+/// and the kanji that could not be named after their first meaning are returned.
+pub fn derive_names(
+    kanjifile: &mut Kanjifile,
+    frequencies: &HashMap<KanjiId, u32>,
+    errors: &mut Errors,
+) -> Vec<NameCollision> {
+    let mut used_names = HashMap::new();
+    for kanji in &mut kanjifile.kanji {
+        let Some(name) = &kanji.name else {
+            continue;
+        };
+        kanji.name_source = Some(NameSource::Curated);
+        if let Some(other) = used_names.insert(name.clone(), kanji.kanji.clone()) {
+            errors.push(Error::RepeatedKanjiName {
+                name: name.clone(),
+                kanji: kanji.kanji.clone(),
+                other,
+            });
+        }
+    }
+
+    let mut unnamed = kanjifile
+        .kanji
+        .iter_mut()
+        .filter(|k| k.name.is_none())
+        .collect::<Vec<_>>();
+    // kanji without a frequency rank are less common than the ones with a rank
+    unnamed.sort_by_key(|k| (frequencies.get(&k.id).copied().unwrap_or(u32::MAX), k.id));
+    let mut derived = 0;
+    let mut collisions = vec![];
+    for kanji in unnamed {
+        let Some(name) = kanji
+            .meanings
+            .iter()
+            .find(|m| !used_names.contains_key(m.as_str()))
+            .cloned()
+        else {
+            if let Some(first) = kanji.meanings.first() {
+                tracing::warn!(
+                    "no name for '{}', all its meanings are already names, such as '{first}' of '{}'",
+                    kanji.kanji,
+                    used_names[first.as_str()]
+                );
+                collisions.push(NameCollision {
+                    id: kanji.id,
+                    kanji: kanji.kanji.clone(),
+                    meaning: first.clone(),
+                    named_kanji: used_names[first.as_str()].clone(),
+                    name: None,
+                });
+            }
+            continue;
+        };
+        if let Some(first) = kanji.meanings.first().filter(|first| **first != name) {
+            tracing::debug!(
+                "named '{}' '{name}' because '{first}' is the name of '{}'",
+                kanji.kanji,
+                used_names[first.as_str()]
+            );
+            collisions.push(NameCollision {
+                id: kanji.id,
+                kanji: kanji.kanji.clone(),
+                meaning: first.clone(),
+                named_kanji: used_names[first.as_str()].clone(),
+                name: Some(name.clone()),
+            });
+        }
+        used_names.insert(name.clone(), kanji.kanji.clone());
+        kanji.name = Some(name);
+        kanji.name_source = Some(NameSource::Derived);
+        derived += 1;
+    }
+    tracing::info!(
+        "derived {derived} kanji names, {} kanji collided with other names",
+        collisions.len()
+    );
+    collisions
+}
+
+fn handle_meanings<'a>(
+    meanings: Vec<kanjidic2::Meaning>,
+    filters: &'a Filters,
+) -> impl Iterator<Item = String> + 'a {
     meanings
         .into_iter()
-        .filter(|m| m.m_lang.is_none())
+        .filter(|m| filters.includes_language(m.m_lang.as_deref()))
         .map(|m| m.value)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jadata::kanjifile::Header;
+
+    fn kanjifile(kanji: &[(&str, Option<&str>, &[&str])]) -> Kanjifile {
+        Kanjifile {
+            header: Header {
+                version: "1".to_string(),
+                kanjidic2_version: "1".to_string(),
+                kanjidic2_date_of_creation: "2023-12-01".to_string(),
+                last_kanji_id: KanjiId(kanji.len() as u32),
+            },
+            kanji: kanji
+                .iter()
+                .zip(1..)
+                .map(|((kanji, name, meanings), id)| Kanji {
+                    id: KanjiId(id),
+                    kanji: kanji.to_string(),
+                    components: vec![],
+                    name: name.map(str::to_string),
+                    name_source: None,
+                    meanings: meanings.iter().map(|m| m.to_string()).collect(),
+                    similar: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    fn names(kanjifile: &Kanjifile) -> Vec<(Option<&str>, Option<NameSource>)> {
+        kanjifile
+            .kanji
+            .iter()
+            .map(|k| (k.name.as_deref(), k.name_source))
+            .collect()
+    }
+
+    #[test]
+    fn more_common_kanji_is_named_first() {
+        // 陽 comes first in the file, but 日 is more common and gets the shared meaning
+        let mut kanjifile = kanjifile(&[
+            ("陽", None, &["sun", "positive"]),
+            ("日", None, &["sun", "day"]),
+        ]);
+        let frequencies = HashMap::from([(KanjiId(1), 800), (KanjiId(2), 1)]);
+        let mut errors = Errors::new();
+        let collisions = derive_names(&mut kanjifile, &frequencies, &mut errors);
+        assert!(errors.is_empty());
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].kanji, "陽");
+        assert_eq!(collisions[0].meaning, "sun");
+        assert_eq!(collisions[0].named_kanji, "日");
+        assert_eq!(collisions[0].name.as_deref(), Some("positive"));
+        assert_eq!(
+            names(&kanjifile),
+            [
+                (Some("positive"), Some(NameSource::Derived)),
+                (Some("sun"), Some(NameSource::Derived)),
+            ]
+        );
+    }
+
+    #[test]
+    fn kanji_without_free_meaning_is_unnamed() {
+        let mut kanjifile = kanjifile(&[
+            ("日", Some("sun"), &["sun", "day"]),
+            ("陽", None, &["sun"]),
+            ("〇", None, &[]),
+        ]);
+        let mut errors = Errors::new();
+        let collisions = derive_names(&mut kanjifile, &HashMap::new(), &mut errors);
+        assert!(errors.is_empty());
+        // a kanji without meanings has nothing to collide
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].kanji, "陽");
+        assert_eq!(collisions[0].named_kanji, "日");
+        assert_eq!(collisions[0].name, None);
+        assert_eq!(
+            names(&kanjifile),
+            [
+                (Some("sun"), Some(NameSource::Curated)),
+                (None, None),
+                (None, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_curated_name() {
+        let mut kanjifile = kanjifile(&[
+            ("日", Some("sun"), &["sun", "day"]),
+            ("陽", Some("sun"), &["sun", "positive"]),
+        ]);
+        let mut errors = Errors::new();
+        derive_names(&mut kanjifile, &HashMap::new(), &mut errors);
+        assert!(errors
+            .to_string()
+            .contains("repeated kanji name 'sun' for kanji '陽', already used for '日'"));
+    }
+}
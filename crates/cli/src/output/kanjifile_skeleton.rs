@@ -1,23 +1,42 @@
-use crate::input::{jmdict::JMdict, kanjidic2::Kanjidic2};
-use jadata::kanjifile::{Header, Kanji, Kanjifile};
-use std::{collections::HashSet, ops::Range};
+use crate::{
+    input::{jmdict::Entry, kanjidic2::Kanjidic2},
+    output::report::{AddedKanji, KanjiSource, KanjifileReport},
+};
+use eyre::ContextCompat;
+use jadata::{
+    id::KanjiId,
+    kanjifile::{self, Header, Kanji, Kanjifile},
+};
+use std::collections::HashSet;
+
+pub mod annotate;
+pub mod candidates;
 
 /// Creates the skeleton for a kanjifile that only contains the bare minimum information for each kanji.
-pub fn create(kd2: Kanjidic2, jmdict: JMdict) -> eyre::Result<Kanjifile> {
+pub fn create(
+    kd2: Kanjidic2,
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+) -> eyre::Result<(Kanjifile, KanjifileReport)> {
     let header = Header {
         version: "".to_string(),
         kanjidic2_version: "".to_string(),
+        kanjidic2_date_of_creation: "".to_string(),
+        last_kanji_id: KanjiId::default(),
     };
     let mut kf = Kanjifile {
         header,
         kanji: Vec::new(),
     };
-    update(&mut kf, kd2, jmdict)?;
-    Ok(kf)
+    let report = update(&mut kf, kd2, jmdict)?;
+    Ok((kf, report))
 }
 
-/// Updates a kanjifile with new kanji from the Kanjidic2.
-pub fn update(kanjifile: &mut Kanjifile, kd2: Kanjidic2, jmdict: JMdict) -> eyre::Result<()> {
+/// Updates a kanjifile with new kanji from the Kanjidic2 and returns a report of the changes.
+pub fn update(
+    kanjifile: &mut Kanjifile,
+    kd2: Kanjidic2,
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+) -> eyre::Result<KanjifileReport> {
     let existing_kanji = kanjifile
         .kanji
         .iter()
@@ -30,78 +49,67 @@ pub fn update(kanjifile: &mut Kanjifile, kd2: Kanjidic2, jmdict: JMdict) -> eyre
         .map(|c| c.literal)
         .collect::<HashSet<_>>();
 
-    let kebs = jmdict
-        .entry
-        .into_iter()
-        .flat_map(|e| e.k_ele)
-        .map(|k| k.keb)
-        .collect::<Vec<_>>();
-    let new_kanji_from_jmdict = kebs
-        .iter()
-        .flat_map(|keb| keb.chars())
-        .inspect(|c| {
-            if *c == '仝' {
-                tracing::info!("here")
+    let mut new_kanji_from_jmdict = HashSet::new();
+    for entry in jmdict {
+        for kele in entry?.k_ele {
+            for char in kele.keb.chars() {
+                let char_s = char.to_string();
+                if kanjifile::is_kanji(char)
+                    && !existing_kanji.contains(&char_s)
+                    && !new_kanji_from_kanjidic.contains(&char_s)
+                {
+                    new_kanji_from_jmdict.insert(char_s);
+                }
             }
-        })
-        .filter(|char| {
-            let char_s = char.to_string();
-            is_kanji(*char)
-                && !existing_kanji.contains(&char_s)
-                && !new_kanji_from_kanjidic.contains(&char_s)
-        })
-        .inspect(|c| {
-            if *c == '仝' {
-                tracing::info!("there")
-            }
-        })
-        .map(|c| c.to_string())
-        .collect::<HashSet<_>>();
+        }
+    }
 
     let mut new_kanji = new_kanji_from_kanjidic
         .into_iter()
-        .chain(new_kanji_from_jmdict.into_iter().inspect(|char| {
+        .map(|kanji| (kanji, KanjiSource::Kanjidic2))
+        .chain(new_kanji_from_jmdict.into_iter().map(|kanji| {
             tracing::info!(
-                "found new kanji in JMdict not present in the kanjidic2/skeleton: '{char}'"
-            )
+                "found new kanji in JMdict not present in the kanjidic2/skeleton: '{kanji}'"
+            );
+            (kanji, KanjiSource::Jmdict)
         }))
         .collect::<Vec<_>>();
-    new_kanji.sort();
-    let mut last_kanji_id = kanjifile.kanji.iter().map(|k| k.id).max().unwrap_or(0);
-    let new_kanji = new_kanji.into_iter().map(|kanji| {
-        last_kanji_id += 1;
-        Kanji {
+    new_kanji.sort_by(|a, b| a.0.cmp(&b.0));
+    // the header may be missing the last id if the skeleton was created by an older version
+    let mut last_kanji_id = kanjifile
+        .kanji
+        .iter()
+        .map(|k| k.id)
+        .max()
+        .unwrap_or_default()
+        .max(kanjifile.header.last_kanji_id);
+    let mut report = KanjifileReport {
+        old_kanjidic2_version: kanjifile.header.kanjidic2_version.clone(),
+        new_kanjidic2_version: kd2.header.file_version.clone(),
+        added: Vec::new(),
+    };
+    for (kanji, source) in new_kanji {
+        last_kanji_id = last_kanji_id.next().wrap_err_with(|| {
+            format!("Ran out of kanji ids after {last_kanji_id} while adding '{kanji}'")
+        })?;
+        report.added.push(AddedKanji {
+            id: last_kanji_id,
+            kanji: kanji.clone(),
+            source,
+        });
+        kanjifile.kanji.push(Kanji {
             id: last_kanji_id,
             kanji,
             name: None,
+            name_source: None,
             components: vec![],
             meanings: vec![],
             similar: vec![],
-        }
-    });
+        });
+    }
     kanjifile.header.kanjidic2_version = kd2.header.file_version;
-    kanjifile.kanji.extend(new_kanji);
+    kanjifile.header.kanjidic2_date_of_creation = kd2.header.date_of_creation;
+    kanjifile.header.last_kanji_id = last_kanji_id;
 
-    Ok(())
+    Ok(report)
 }
-
-fn is_kanji(c: char) -> bool {
-    let c = c as u32;
-    CJK.contains(&c)
-        || CJK_EXT_B.contains(&c)
-        || CJK_EXT_C.contains(&c)
-        || CJK_EXT_D.contains(&c)
-        || CJK_EXT_E.contains(&c)
-        || CJK_EXT_F.contains(&c)
-        || CJK_EXT_G.contains(&c)
-        || CJK_EXT_H.contains(&c)
-}
-
-const CJK: Range<u32> = 0x4E00..0x9FFF;
-const CJK_EXT_B: Range<u32> = 0x20000..0x2A6DF;
-const CJK_EXT_C: Range<u32> = 0x2A700..0x2B73F;
-const CJK_EXT_D: Range<u32> = 0x2B740..0x2B81F;
-const CJK_EXT_E: Range<u32> = 0x2B820..0x2CEAF;
-const CJK_EXT_F: Range<u32> = 0x2CEB0..0x2EBEF;
-const CJK_EXT_G: Range<u32> = 0x30000..0x3134F;
-const CJK_EXT_H: Range<u32> = 0x31350..0x323AF;
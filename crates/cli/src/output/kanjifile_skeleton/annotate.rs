@@ -0,0 +1,370 @@
+//! Imports manual annotations into the kanjifile skeleton: the names of kanji and groups of visually similar kanji.
+//! The annotations are read from tables, see [`crate::input::table`].
+
+use crate::error::{Error, Errors};
+use jadata::kanjifile::Kanjifile;
+use std::collections::HashMap;
+
+/// Sets the names of kanji from rows of a kanji and its name.
+/// A name that differs from the existing one is a conflict recorded in `errors`, unless `overwrite` is set.
+/// A name that is already the name of another kanji is recorded in `errors` and not set.
+pub fn names(
+    kanjifile: &mut Kanjifile,
+    rows: &[Vec<String>],
+    overwrite: bool,
+    errors: &mut Errors,
+) {
+    let mut named = kanjifile
+        .kanji
+        .iter()
+        .filter_map(|k| Some((k.name.clone()?, k.kanji.clone())))
+        .collect::<HashMap<_, _>>();
+    let mut kanji_map = kanjifile
+        .kanji
+        .iter_mut()
+        .map(|k| (k.kanji.clone(), k))
+        .collect::<HashMap<_, _>>();
+    let mut imported = 0;
+    for row in rows {
+        let [kanji, name] = row.as_slice() else {
+            errors.push(Error::InvalidAnnotation {
+                row: row.join(", "),
+                reason: "expected a kanji and a name",
+            });
+            continue;
+        };
+        let Some(skeleton) = kanji_map.get_mut(kanji) else {
+            errors.push(Error::UnknownAnnotatedKanji {
+                kanji: kanji.clone(),
+            });
+            continue;
+        };
+        match named.get(name) {
+            Some(other) if other != kanji => {
+                errors.push(Error::RepeatedKanjiName {
+                    name: name.clone(),
+                    kanji: kanji.clone(),
+                    other: other.clone(),
+                });
+                continue;
+            }
+            _ => {}
+        }
+        match &skeleton.name {
+            Some(existing) if existing == name => {}
+            Some(existing) if !overwrite => {
+                errors.push(Error::ConflictingKanjiName {
+                    kanji: kanji.clone(),
+                    existing: existing.clone(),
+                    new: name.clone(),
+                });
+            }
+            existing => {
+                if let Some(existing) = existing {
+                    tracing::warn!("replacing the name '{existing}' of '{kanji}' with '{name}'");
+                    named.remove(existing);
+                }
+                named.insert(name.clone(), kanji.clone());
+                skeleton.name = Some(name.clone());
+                imported += 1;
+            }
+        }
+    }
+    tracing::info!("set {imported} kanji names");
+}
+
+/// Marks the kanji in each row as similar to each other.
+pub fn similar(kanjifile: &mut Kanjifile, rows: &[Vec<String>], errors: &mut Errors) {
+    let positions = kanjifile
+        .kanji
+        .iter()
+        .enumerate()
+        .map(|(idx, k)| (k.kanji.clone(), idx))
+        .collect::<HashMap<_, _>>();
+    let mut added = 0;
+    for row in rows {
+        if row.len() < 2 {
+            errors.push(Error::InvalidAnnotation {
+                row: row.join(", "),
+                reason: "expected at least two similar kanji",
+            });
+            continue;
+        }
+        let unknown = row
+            .iter()
+            .filter(|kanji| !positions.contains_key(*kanji))
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            for kanji in unknown {
+                errors.push(Error::UnknownAnnotatedKanji {
+                    kanji: kanji.clone(),
+                });
+            }
+            continue;
+        }
+        for kanji in row {
+            let similar = &mut kanjifile.kanji[positions[kanji]].similar;
+            for other in row {
+                if other != kanji && !similar.contains(other) {
+                    similar.push(other.clone());
+                    added += 1;
+                }
+            }
+        }
+    }
+    tracing::info!("added {added} similar kanji");
+}
+
+/// Makes the similar kanji symmetric, so that each kanji is similar to the kanji similar to it.
+/// Similar kanji that are not in the skeleton are recorded in `errors`.
+pub fn make_similar_symmetric(kanjifile: &mut Kanjifile, errors: &mut Errors) {
+    let positions = kanjifile
+        .kanji
+        .iter()
+        .enumerate()
+        .map(|(idx, k)| (k.kanji.clone(), idx))
+        .collect::<HashMap<_, _>>();
+    let mut missing = vec![];
+    for kanji in &kanjifile.kanji {
+        for other in &kanji.similar {
+            match positions.get(other) {
+                Some(&idx) if !kanjifile.kanji[idx].similar.contains(&kanji.kanji) => {
+                    missing.push((idx, kanji.kanji.clone()));
+                }
+                Some(_) => {}
+                None => errors.push(Error::UnknownAnnotatedKanji {
+                    kanji: other.clone(),
+                }),
+            }
+        }
+    }
+    for (idx, kanji) in missing {
+        tracing::info!(
+            "marking '{kanji}' as similar to '{}' to keep the similar kanji symmetric",
+            kanjifile.kanji[idx].kanji
+        );
+        kanjifile.kanji[idx].similar.push(kanji);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jadata::{
+        id::KanjiId,
+        kanjifile::{Header, Kanji},
+    };
+
+    fn kanjifile(kanji: &[(&str, Option<&str>, &[&str])]) -> Kanjifile {
+        Kanjifile {
+            header: Header {
+                version: "1".to_string(),
+                kanjidic2_version: "1".to_string(),
+                kanjidic2_date_of_creation: "2023-12-01".to_string(),
+                last_kanji_id: KanjiId(kanji.len() as u32),
+            },
+            kanji: kanji
+                .iter()
+                .zip(1..)
+                .map(|((kanji, name, similar), id)| Kanji {
+                    id: KanjiId(id),
+                    kanji: kanji.to_string(),
+                    components: vec![],
+                    name: name.map(str::to_string),
+                    name_source: None,
+                    meanings: vec![],
+                    similar: similar.iter().map(|s| s.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+
+    fn names_of(kanjifile: &Kanjifile) -> Vec<Option<&str>> {
+        kanjifile.kanji.iter().map(|k| k.name.as_deref()).collect()
+    }
+
+    fn similar_of(kanjifile: &Kanjifile) -> Vec<Vec<&str>> {
+        kanjifile
+            .kanji
+            .iter()
+            .map(|k| k.similar.iter().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn sets_names() {
+        let mut kanjifile = kanjifile(&[("人", None, &[]), ("入", Some("enter"), &[])]);
+        let mut errors = Errors::new();
+        names(
+            &mut kanjifile,
+            &rows(&[&["人", "person"], &["入", "enter"]]),
+            false,
+            &mut errors,
+        );
+        assert!(errors.is_empty(), "{errors}");
+        assert_eq!(names_of(&kanjifile), [Some("person"), Some("enter")]);
+    }
+
+    #[test]
+    fn invalid_name_rows_are_rejected() {
+        let mut kanjifile = kanjifile(&[("人", None, &[])]);
+        let mut errors = Errors::new();
+        names(
+            &mut kanjifile,
+            &rows(&[&["人"], &["人", "person", "human"]]),
+            false,
+            &mut errors,
+        );
+        let errors = errors.to_string();
+        assert!(errors.contains("invalid annotation row '人'"), "{errors}");
+        assert!(
+            errors.contains("invalid annotation row '人, person, human'"),
+            "{errors}"
+        );
+        assert_eq!(names_of(&kanjifile), [None]);
+    }
+
+    #[test]
+    fn differing_name_conflicts() {
+        let mut kanjifile = kanjifile(&[("人", Some("person"), &[])]);
+        let mut errors = Errors::new();
+        names(
+            &mut kanjifile,
+            &rows(&[&["人", "human"]]),
+            false,
+            &mut errors,
+        );
+        assert!(errors
+            .to_string()
+            .contains("the name 'human' for kanji '人' conflicts with the existing name 'person'"));
+        assert_eq!(names_of(&kanjifile), [Some("person")]);
+    }
+
+    #[test]
+    fn differing_name_is_overwritten() {
+        let mut kanjifile = kanjifile(&[("人", Some("person"), &[])]);
+        let mut errors = Errors::new();
+        names(
+            &mut kanjifile,
+            &rows(&[&["人", "human"]]),
+            true,
+            &mut errors,
+        );
+        assert!(errors.is_empty(), "{errors}");
+        assert_eq!(names_of(&kanjifile), [Some("human")]);
+    }
+
+    #[test]
+    fn unknown_kanji_is_rejected() {
+        let mut kanjifile = kanjifile(&[("人", None, &[])]);
+        let mut errors = Errors::new();
+        names(
+            &mut kanjifile,
+            &rows(&[&["入", "enter"]]),
+            false,
+            &mut errors,
+        );
+        assert!(errors
+            .to_string()
+            .contains("no skeleton for annotated kanji '入'"));
+        assert_eq!(names_of(&kanjifile), [None]);
+    }
+
+    #[test]
+    fn name_of_another_kanji_is_rejected() {
+        let mut kanjifile = kanjifile(&[
+            ("人", Some("person"), &[]),
+            ("入", None, &[]),
+            ("八", None, &[]),
+        ]);
+        let mut errors = Errors::new();
+        names(
+            &mut kanjifile,
+            &rows(&[&["入", "person"], &["八", "eight"], &["入", "eight"]]),
+            false,
+            &mut errors,
+        );
+        let errors = errors.to_string();
+        assert!(
+            errors.contains("repeated kanji name 'person' for kanji '入', already used for '人'"),
+            "{errors}"
+        );
+        assert!(
+            errors.contains("repeated kanji name 'eight' for kanji '入', already used for '八'"),
+            "{errors}"
+        );
+        assert_eq!(names_of(&kanjifile), [Some("person"), None, Some("eight")]);
+    }
+
+    #[test]
+    fn overwritten_name_can_be_reused() {
+        let mut kanjifile = kanjifile(&[("人", Some("person"), &[]), ("入", None, &[])]);
+        let mut errors = Errors::new();
+        names(
+            &mut kanjifile,
+            &rows(&[&["人", "human"], &["入", "person"]]),
+            true,
+            &mut errors,
+        );
+        assert!(errors.is_empty(), "{errors}");
+        assert_eq!(names_of(&kanjifile), [Some("human"), Some("person")]);
+    }
+
+    #[test]
+    fn similar_kanji_are_grouped() {
+        let mut kanjifile = kanjifile(&[
+            ("人", None, &["入"]),
+            ("入", None, &["人"]),
+            ("八", None, &[]),
+        ]);
+        let mut errors = Errors::new();
+        similar(&mut kanjifile, &rows(&[&["人", "入", "八"]]), &mut errors);
+        assert!(errors.is_empty(), "{errors}");
+        assert_eq!(
+            similar_of(&kanjifile),
+            [vec!["入", "八"], vec!["人", "八"], vec!["人", "入"]]
+        );
+    }
+
+    #[test]
+    fn invalid_similar_rows_are_rejected() {
+        let mut kanjifile = kanjifile(&[("人", None, &[]), ("入", None, &[])]);
+        let mut errors = Errors::new();
+        similar(
+            &mut kanjifile,
+            &rows(&[&["人"], &["人", "入", "亜"]]),
+            &mut errors,
+        );
+        let errors = errors.to_string();
+        assert!(errors.contains("invalid annotation row '人'"), "{errors}");
+        assert!(
+            errors.contains("no skeleton for annotated kanji '亜'"),
+            "{errors}"
+        );
+        assert_eq!(similar_of(&kanjifile), [Vec::<&str>::new(), vec![]]);
+    }
+
+    #[test]
+    fn similar_kanji_are_made_symmetric() {
+        let mut kanjifile = kanjifile(&[
+            ("人", None, &["入", "亜"]),
+            ("入", None, &[]),
+            ("八", None, &["入"]),
+        ]);
+        let mut errors = Errors::new();
+        make_similar_symmetric(&mut kanjifile, &mut errors);
+        assert!(errors
+            .to_string()
+            .contains("no skeleton for annotated kanji '亜'"));
+        assert_eq!(
+            similar_of(&kanjifile),
+            [vec!["入", "亜"], vec!["人", "八"], vec!["入"]]
+        );
+    }
+}
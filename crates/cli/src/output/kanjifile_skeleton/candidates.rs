@@ -0,0 +1,351 @@
+//! Proposes candidates for similar kanji.
+//! Kanji that share most of their KRADFILE components and have about the same number of strokes
+//! tend to look alike, such as 人 and 入 or 未 and 末.
+//!
+//! The candidates are written to a review table in the format read by `kanji-annotate --similar`,
+//! so that the accepted candidates can be merged into the skeleton after removing the rejected rows.
+
+use crate::input::{kanjidic2::Kanjidic2, kradfile::Kradfile, table};
+use eyre::WrapErr;
+use jadata::kanjifile::Kanjifile;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+/// Settings for finding candidates.
+#[derive(Debug, Clone, Copy)]
+pub struct CandidateOptions {
+    /// The smallest share of the components of the two kanji that they have in common, from 0 to 1.
+    pub min_overlap: f64,
+    /// The largest difference between the stroke counts of the two kanji.
+    pub max_stroke_difference: u32,
+    /// The number of the best candidates kept for each kanji.
+    pub per_kanji: usize,
+}
+
+/// A pair of kanji that may be similar.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub kanji: String,
+    pub other: String,
+    /// The number of shared components divided by the number of components in either kanji.
+    pub overlap: f64,
+    pub shared_components: usize,
+    pub stroke_difference: u32,
+}
+
+/// Finds the candidates for similar kanji among the kanji in the skeleton, best first.
+/// Kanji already marked as similar are left out, as are kanji without components or stroke counts.
+pub fn find(
+    kanjifile: &Kanjifile,
+    kd2: &Kanjidic2,
+    kf: &Kradfile,
+    options: CandidateOptions,
+) -> Vec<Candidate> {
+    // the first stroke count is the accepted one, the rest are common miscounts
+    let stroke_counts = kd2
+        .character
+        .iter()
+        .filter_map(|c| {
+            Some((
+                c.literal.as_str(),
+                c.misc.stroke_count.first()?.parse().ok()?,
+            ))
+        })
+        .collect::<HashMap<&str, u32>>();
+    let components = kanjifile
+        .kanji
+        .iter()
+        .filter(|k| stroke_counts.contains_key(k.kanji.as_str()))
+        .filter_map(|k| {
+            let components = kf.kanji_to_components.get(&k.kanji)?;
+            Some((k.kanji.as_str(), components.iter().collect::<BTreeSet<_>>()))
+        })
+        .collect::<HashMap<_, _>>();
+    let existing = kanjifile
+        .kanji
+        .iter()
+        .flat_map(|k| {
+            k.similar
+                .iter()
+                .map(move |s| (k.kanji.as_str(), s.as_str()))
+        })
+        .collect::<HashSet<_>>();
+    let mut kanji_with_component: HashMap<&String, Vec<&str>> = HashMap::new();
+    for (kanji, components) in &components {
+        for component in components {
+            kanji_with_component
+                .entry(component)
+                .or_default()
+                .push(kanji);
+        }
+    }
+
+    let mut pairs = HashMap::new();
+    for (kanji, kanji_components) in &components {
+        let mut shared: HashMap<&str, usize> = HashMap::new();
+        for component in kanji_components {
+            for other in &kanji_with_component[component] {
+                if other != kanji {
+                    *shared.entry(other).or_default() += 1;
+                }
+            }
+        }
+        let mut candidates = shared
+            .into_iter()
+            .filter(|(other, _)| !existing.contains(&(*kanji, *other)))
+            .filter_map(|(other, shared_components)| {
+                let union = kanji_components.union(&components[other]).count();
+                let overlap = shared_components as f64 / union as f64;
+                let stroke_difference = stroke_counts[kanji].abs_diff(stroke_counts[other]);
+                (overlap >= options.min_overlap
+                    && stroke_difference <= options.max_stroke_difference)
+                    .then_some(Candidate {
+                        kanji: kanji.to_string(),
+                        other: other.to_string(),
+                        overlap,
+                        shared_components,
+                        stroke_difference,
+                    })
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(rank);
+        // each pair is found from both kanji, so it is stored in a fixed order
+        for mut candidate in candidates.into_iter().take(options.per_kanji) {
+            if candidate.other < candidate.kanji {
+                std::mem::swap(&mut candidate.kanji, &mut candidate.other);
+            }
+            pairs.insert(
+                (candidate.kanji.clone(), candidate.other.clone()),
+                candidate,
+            );
+        }
+    }
+    let mut candidates = pairs.into_values().collect::<Vec<_>>();
+    candidates.sort_by(rank);
+    candidates
+}
+
+/// Writes the candidates as a review table, with the scores of each pair in a comment above it.
+/// The table is tab-separated if the path ends in `.tsv` and comma-separated otherwise.
+pub fn write(candidates: &[Candidate], path: &Path) -> eyre::Result<()> {
+    let delimiter = char::from(table::delimiter(path));
+    let mut contents = String::new();
+    let _ = writeln!(
+        contents,
+        "# candidates for similar kanji, remove the rows of the kanji that are not similar\n\
+         # and import the rest with `kanji-annotate --similar {}`",
+        path.display()
+    );
+    for candidate in candidates {
+        let _ = writeln!(
+            contents,
+            "# {:.2} overlap, {} shared components, stroke count difference {}\n{}{delimiter}{}",
+            candidate.overlap,
+            candidate.shared_components,
+            candidate.stroke_difference,
+            candidate.kanji,
+            candidate.other
+        );
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+        .wrap_err_with(|| format!("Failed to write candidates at '{}'", path.display()))?;
+    Ok(())
+}
+
+// the most overlapping components first, then the closest stroke counts
+fn rank(a: &Candidate, b: &Candidate) -> std::cmp::Ordering {
+    b.overlap
+        .total_cmp(&a.overlap)
+        .then(a.stroke_difference.cmp(&b.stroke_difference))
+        .then(b.shared_components.cmp(&a.shared_components))
+        .then_with(|| (&a.kanji, &a.other).cmp(&(&b.kanji, &b.other)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::kradfile::Kradfile;
+    use encoding_rs::EUC_JP;
+    use jadata::{
+        id::KanjiId,
+        kanjifile::{Header, Kanji},
+    };
+    use std::fs::File;
+
+    const OPTIONS: CandidateOptions = CandidateOptions {
+        min_overlap: 0.5,
+        max_stroke_difference: 2,
+        per_kanji: 10,
+    };
+
+    fn kanjifile(kanji: &[(&str, &[&str])]) -> Kanjifile {
+        Kanjifile {
+            header: Header {
+                version: "1".to_string(),
+                kanjidic2_version: "1".to_string(),
+                kanjidic2_date_of_creation: "2023-12-01".to_string(),
+                last_kanji_id: KanjiId(kanji.len() as u32),
+            },
+            kanji: kanji
+                .iter()
+                .zip(1..)
+                .map(|((kanji, similar), id)| Kanji {
+                    id: KanjiId(id),
+                    kanji: kanji.to_string(),
+                    components: vec![],
+                    name: None,
+                    name_source: None,
+                    meanings: vec![],
+                    similar: similar.iter().map(|s| s.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn kanjidic2(stroke_counts: &[(&str, u32)]) -> Kanjidic2 {
+        let mut xml = "<kanjidic2><header><file_version>4</file_version>\
+            <database_version>2023-335</database_version>\
+            <date_of_creation>2023-12-01</date_of_creation></header>"
+            .to_string();
+        for (literal, stroke_count) in stroke_counts {
+            let _ = write!(
+                xml,
+                "<character><literal>{literal}</literal>\
+                 <codepoint><cp_value cp_type=\"ucs\">0</cp_value></codepoint>\
+                 <radical><rad_value rad_type=\"classical\">1</rad_value></radical>\
+                 <misc><stroke_count>{stroke_count}</stroke_count></misc></character>"
+            );
+        }
+        xml.push_str("</kanjidic2>");
+        serde_xml_rs::from_str(&xml).unwrap()
+    }
+
+    fn kradfile(components: &[(&str, &[&str])]) -> Kradfile {
+        Kradfile {
+            kanji_to_components: components
+                .iter()
+                .map(|(kanji, components)| {
+                    let components = components.iter().map(|c| c.to_string()).collect();
+                    (kanji.to_string(), components)
+                })
+                .collect(),
+        }
+    }
+
+    fn pairs(candidates: &[Candidate]) -> Vec<(&str, &str)> {
+        candidates
+            .iter()
+            .map(|c| (c.kanji.as_str(), c.other.as_str()))
+            .collect()
+    }
+
+    // 一, 二 and 三 share all their components, 四 has one more,
+    // and 三 has one stroke more than the others
+    fn find_example(options: CandidateOptions) -> Vec<Candidate> {
+        let kanjifile = kanjifile(&[("一", &[]), ("二", &[]), ("三", &[]), ("四", &[])]);
+        let kd2 = kanjidic2(&[("一", 5), ("二", 5), ("三", 6), ("四", 5)]);
+        let kf = kradfile(&[
+            ("一", &["a", "b"]),
+            ("二", &["a", "b"]),
+            ("三", &["a", "b"]),
+            ("四", &["a", "b", "c"]),
+        ]);
+        find(&kanjifile, &kd2, &kf, options)
+    }
+
+    #[test]
+    fn candidates_are_ranked_by_overlap_then_stroke_difference() {
+        let candidates = find_example(OPTIONS);
+        // each pair is listed once, with the smaller codepoint first
+        assert_eq!(
+            pairs(&candidates),
+            [
+                ("一", "二"),
+                ("一", "三"),
+                ("三", "二"),
+                ("一", "四"),
+                ("二", "四"),
+                ("三", "四"),
+            ]
+        );
+        let scores = candidates
+            .iter()
+            .map(|c| (c.shared_components, c.stroke_difference))
+            .collect::<Vec<_>>();
+        assert_eq!(scores, [(2, 0), (2, 1), (2, 1), (2, 0), (2, 0), (2, 1)]);
+        assert_eq!(candidates[0].overlap, 1.0);
+        assert!((candidates[3].overlap - 2.0 / 3.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn candidates_are_cut_off_per_kanji() {
+        let candidates = find_example(CandidateOptions {
+            per_kanji: 1,
+            ..OPTIONS
+        });
+        assert_eq!(
+            pairs(&candidates),
+            [("一", "二"), ("一", "三"), ("一", "四")]
+        );
+    }
+
+    #[test]
+    fn candidates_are_filtered_by_overlap_and_stroke_difference() {
+        let candidates = find_example(CandidateOptions {
+            min_overlap: 1.0,
+            max_stroke_difference: 0,
+            ..OPTIONS
+        });
+        assert_eq!(pairs(&candidates), [("一", "二")]);
+    }
+
+    #[test]
+    fn kradfile_and_kanjidic2_fixture() {
+        // 人 and 入 share their component, but are already similar
+        let kradfile = "# comment\n未 : 一 木\n末 : 一 木\n人 : 人\n入 : 人\n本 : 一 木 |\n";
+        let (kradfile, _, _) = EUC_JP.encode(kradfile);
+        let kf = Kradfile::from(kradfile.as_ref()).unwrap();
+        let kd2 = kanjidic2(&[("未", 5), ("末", 5), ("人", 2), ("入", 2), ("本", 5)]);
+        let kanjifile = kanjifile(&[
+            ("人", &["入"]),
+            ("入", &["人"]),
+            ("未", &[]),
+            ("末", &[]),
+            ("本", &[]),
+        ]);
+        let candidates = find(
+            &kanjifile,
+            &kd2,
+            &kf,
+            CandidateOptions {
+                min_overlap: 0.7,
+                ..OPTIONS
+            },
+        );
+        assert_eq!(pairs(&candidates), [("未", "末")]);
+    }
+
+    #[test]
+    fn written_table_is_read_back() {
+        let candidates = find_example(OPTIONS);
+        let dir = std::env::temp_dir().join(format!("jadata-candidates-{}", std::process::id()));
+        for file in ["candidates.csv", "candidates.tsv"] {
+            let path = dir.join(file);
+            write(&candidates, &path).unwrap();
+            let rows = table::read(&path, File::open(&path).unwrap()).unwrap();
+            let rows = rows
+                .iter()
+                .map(|row| (row[0].as_str(), row[1].as_str()))
+                .collect::<Vec<_>>();
+            assert_eq!(rows, pairs(&candidates));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
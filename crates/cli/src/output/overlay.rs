@@ -0,0 +1,336 @@
+//! Manual corrections to the generated files.
+//! Filling a skeleton replaces the data derived from the input files, so corrections to meanings, readings and such
+//! are kept in an overlay file and applied to the filled skeletons instead.
+//!
+//! The overlay contains the changes to each entry keyed by its id, and the changes to each field keyed by the field name.
+//! The operations of a field are applied in order, and a kanji name set by the overlay counts as curated:
+//! ```json
+//! {
+//!   "kanji": {
+//!     "12": { "name": [{ "set": "sun" }] }
+//!   },
+//!   "words": {
+//!     "34": { "meanings": [{ "remove": "to compose" }, { "append": "to draw" }] }
+//!   }
+//! }
+//! ```
+
+use crate::error::{Error, Errors};
+use jadata::{
+    id::{KanjiId, WordId},
+    kanjifile::{Kanji, Kanjifile, NameSource},
+    wordfile::{Word, Wordfile},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+/// Manual corrections applied to the filled skeletons.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Overlay {
+    pub kanji: BTreeMap<KanjiId, Patch>,
+    pub words: BTreeMap<WordId, Patch>,
+}
+
+/// The operations applied to the fields of a single entry, keyed by the field name.
+pub type Patch = BTreeMap<String, Vec<Operation>>;
+
+/// A change to a single field.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// Replaces the value of the field.
+    Set(Value),
+    /// Adds a value to the end of a list field.
+    Append(Value),
+    /// Removes all occurrences of a value from a list field.
+    Remove(Value),
+}
+
+impl Overlay {
+    /// Applies the changes to the kanji of the kanjifile.
+    /// Changes to unknown kanji or fields, and changes that do not fit the field, are recorded in `errors`.
+    /// Names set by the overlay are curated, and if any are set, names used for multiple kanji are recorded in `errors`.
+    pub fn apply_kanji(&self, kanjifile: &mut Kanjifile, errors: &mut Errors) -> eyre::Result<()> {
+        apply(&self.kanji, &mut kanjifile.kanji, errors)?;
+        if self.kanji.values().any(|patch| patch.contains_key("name")) {
+            check_names(kanjifile, errors);
+        }
+        Ok(())
+    }
+
+    /// Applies the changes to the words of the wordfile.
+    /// Changes to unknown words or fields, and changes that do not fit the field, are recorded in `errors`.
+    pub fn apply_words(&self, wordfile: &mut Wordfile, errors: &mut Errors) -> eyre::Result<()> {
+        apply(&self.words, &mut wordfile.words, errors)
+    }
+}
+
+/// An entry of a generated file that can be changed by an overlay.
+trait Entry: Serialize + DeserializeOwned {
+    type Id: Copy + Ord + Display;
+
+    /// The kind of the entry, for error messages.
+    const KIND: &'static str;
+    /// The fields that can be changed. The id can not be changed.
+    const FIELDS: &'static [&'static str];
+
+    fn id(&self) -> Self::Id;
+
+    /// Updates the fields that depend on the patched ones.
+    fn after_patch(&mut self, _patch: &Patch) {}
+}
+
+impl Entry for Kanji {
+    type Id = KanjiId;
+
+    const KIND: &'static str = "kanji";
+    const FIELDS: &'static [&'static str] = &[
+        "kanji",
+        "components",
+        "name",
+        "name_source",
+        "meanings",
+        "similar",
+    ];
+
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+
+    // a name from the overlay is chosen by hand, unless the overlay sets the source as well
+    fn after_patch(&mut self, patch: &Patch) {
+        if patch.contains_key("name") && !patch.contains_key("name_source") {
+            self.name_source = self.name.is_some().then_some(NameSource::Curated);
+        }
+    }
+}
+
+impl Entry for Word {
+    type Id = WordId;
+
+    const KIND: &'static str = "word";
+    const FIELDS: &'static [&'static str] = &["jmdict_id", "written_forms", "meanings", "readings"];
+
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+fn apply<T: Entry>(
+    patches: &BTreeMap<T::Id, Patch>,
+    entries: &mut [T],
+    errors: &mut Errors,
+) -> eyre::Result<()> {
+    let mut patches = patches
+        .iter()
+        .map(|(id, patch)| (*id, patch))
+        .collect::<BTreeMap<_, _>>();
+    for entry in entries {
+        let Some(patch) = patches.remove(&entry.id()) else {
+            continue;
+        };
+        let id = entry.id();
+        let error = |reason: String| Error::InvalidOverlay {
+            entry: format!("{} {id}", T::KIND),
+            reason,
+        };
+        // the entry is edited through its JSON representation so that every field can be changed the same way
+        let Value::Object(mut object) = serde_json::to_value(&*entry)? else {
+            eyre::bail!("{} {id} is not serialized as an object", T::KIND);
+        };
+        let mut valid = true;
+        for (field, operations) in patch {
+            if !T::FIELDS.contains(&field.as_str()) {
+                errors.push(error(format!("unknown field '{field}'")));
+                valid = false;
+                continue;
+            }
+            // empty fields are left out of the JSON
+            let value = object.entry(field.clone()).or_insert(Value::Null);
+            for operation in operations {
+                if let Err(reason) = apply_operation(value, operation) {
+                    errors.push(error(format!("field '{field}': {reason}")));
+                    valid = false;
+                }
+            }
+        }
+        if !valid {
+            continue;
+        }
+        match serde_json::from_value(Value::Object(object)) {
+            Ok(patched) => {
+                tracing::debug!("applied overlay to {} {id}", T::KIND);
+                *entry = patched;
+                entry.after_patch(patch);
+            }
+            Err(err) => errors.push(error(err.to_string())),
+        }
+    }
+    // the patches that were not removed refer to ids not in the file
+    for id in patches.into_keys() {
+        errors.push(Error::UnknownOverlayId {
+            kind: T::KIND,
+            id: id.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// the names of the kanji are unique, also after the overlay has changed some of them
+fn check_names(kanjifile: &Kanjifile, errors: &mut Errors) {
+    let mut used_names = HashMap::new();
+    for kanji in &kanjifile.kanji {
+        let Some(name) = &kanji.name else {
+            continue;
+        };
+        if let Some(other) = used_names.insert(name, &kanji.kanji) {
+            errors.push(Error::RepeatedKanjiName {
+                name: name.clone(),
+                kanji: kanji.kanji.clone(),
+                other: other.clone(),
+            });
+        }
+    }
+}
+
+fn apply_operation(value: &mut Value, operation: &Operation) -> Result<(), String> {
+    match operation {
+        Operation::Set(new) => *value = new.clone(),
+        Operation::Append(item) => {
+            let Some(items) = as_list(value) else {
+                return Err(format!("cannot append {item}, the field is not a list"));
+            };
+            items.push(item.clone());
+        }
+        Operation::Remove(item) => {
+            let Some(items) = as_list(value) else {
+                return Err(format!("cannot remove {item}, the field is not a list"));
+            };
+            let len = items.len();
+            items.retain(|i| i != item);
+            if items.len() == len {
+                return Err(format!(
+                    "cannot remove {item}, the field does not contain it"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// empty lists are left out of the JSON and show up as null
+fn as_list(value: &mut Value) -> Option<&mut Vec<Value>> {
+    if value.is_null() {
+        *value = Value::Array(vec![]);
+    }
+    value.as_array_mut()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jadata::kanjifile::Header;
+    use serde_json::json;
+
+    fn kanjifile(kanji: &[(&str, Option<&str>)]) -> Kanjifile {
+        Kanjifile {
+            header: Header {
+                version: "1".to_string(),
+                kanjidic2_version: "1".to_string(),
+                kanjidic2_date_of_creation: "2023-12-01".to_string(),
+                last_kanji_id: KanjiId(kanji.len() as u32),
+            },
+            kanji: kanji
+                .iter()
+                .zip(1..)
+                .map(|((kanji, name), id)| Kanji {
+                    id: KanjiId(id),
+                    kanji: kanji.to_string(),
+                    components: vec![],
+                    name: name.map(str::to_string),
+                    name_source: name.map(|_| NameSource::Derived),
+                    meanings: vec![],
+                    similar: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    fn overlay(overlay: Value) -> Overlay {
+        serde_json::from_value(overlay).unwrap()
+    }
+
+    #[test]
+    fn set() {
+        let mut value = json!(["sun"]);
+        apply_operation(&mut value, &Operation::Set(json!(["day"]))).unwrap();
+        assert_eq!(value, json!(["day"]));
+    }
+
+    #[test]
+    fn append_to_empty_field() {
+        let mut value = Value::Null;
+        apply_operation(&mut value, &Operation::Append(json!("day"))).unwrap();
+        assert_eq!(value, json!(["day"]));
+        let mut value = json!([]);
+        apply_operation(&mut value, &Operation::Append(json!("day"))).unwrap();
+        assert_eq!(value, json!(["day"]));
+    }
+
+    #[test]
+    fn remove_missing_value() {
+        let mut value = json!(["sun"]);
+        assert!(apply_operation(&mut value, &Operation::Remove(json!("day"))).is_err());
+        assert_eq!(value, json!(["sun"]));
+        let mut value = json!("sun");
+        assert!(apply_operation(&mut value, &Operation::Remove(json!("sun"))).is_err());
+    }
+
+    #[test]
+    fn unknown_field_and_id() {
+        let mut kanjifile = kanjifile(&[("日", Some("sun"))]);
+        let mut errors = Errors::new();
+        overlay(json!({
+            "kanji": {
+                "1": { "reading": [{ "set": "にち" }] },
+                "2": { "name": [{ "set": "moon" }] }
+            }
+        }))
+        .apply_kanji(&mut kanjifile, &mut errors)
+        .unwrap();
+        let errors = errors.to_string();
+        assert!(errors.contains("unknown field 'reading'"));
+        assert!(errors.contains("unknown kanji 2"));
+        assert_eq!(kanjifile.kanji[0].name.as_deref(), Some("sun"));
+    }
+
+    #[test]
+    fn set_name_is_curated() {
+        let mut kanjifile = kanjifile(&[("日", Some("sun")), ("月", Some("moon"))]);
+        let mut errors = Errors::new();
+        overlay(json!({ "kanji": { "1": { "name": [{ "set": "day" }] } } }))
+            .apply_kanji(&mut kanjifile, &mut errors)
+            .unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(kanjifile.kanji[0].name.as_deref(), Some("day"));
+        assert_eq!(kanjifile.kanji[0].name_source, Some(NameSource::Curated));
+        assert_eq!(kanjifile.kanji[1].name_source, Some(NameSource::Derived));
+    }
+
+    #[test]
+    fn set_name_is_unique() {
+        let mut kanjifile = kanjifile(&[("日", Some("sun")), ("月", Some("moon"))]);
+        let mut errors = Errors::new();
+        overlay(json!({ "kanji": { "2": { "name": [{ "set": "sun" }] } } }))
+            .apply_kanji(&mut kanjifile, &mut errors)
+            .unwrap();
+        assert!(errors
+            .to_string()
+            .contains("repeated kanji name 'sun' for kanji '月'"));
+    }
+}
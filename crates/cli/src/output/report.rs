@@ -0,0 +1,472 @@
+//! Reports of the changes made by skeleton updates, and of the kanji names that collided when filling the kanjifile.
+//! They are meant for reviewing an update without reading through the diff of the skeleton,
+//! and are written as JSON or as Markdown that can be attached to a pull request.
+
+use eyre::WrapErr;
+use jadata::id::{JmdictSeq, KanjiId, WordId};
+use serde::Serialize;
+use std::{fmt::Write as _, fs, path::Path};
+
+/// A report that can be written as JSON or Markdown.
+pub trait Report: Serialize {
+    /// Renders the report as a Markdown document.
+    fn to_markdown(&self) -> String;
+}
+
+/// The changes made by a kanjifile skeleton update.
+#[derive(Debug, Default, Serialize)]
+pub struct KanjifileReport {
+    pub old_kanjidic2_version: String,
+    pub new_kanjidic2_version: String,
+    pub added: Vec<AddedKanji>,
+}
+
+/// A kanji added to the kanjifile skeleton.
+#[derive(Debug, Serialize)]
+pub struct AddedKanji {
+    pub id: KanjiId,
+    pub kanji: String,
+    pub source: KanjiSource,
+}
+
+/// The input file a new kanji was found in.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KanjiSource {
+    Kanjidic2,
+    /// The kanji is used in a JMdict written form but missing from KANJIDIC2.
+    Jmdict,
+}
+
+/// The kanji that could not be named after their first meaning because another kanji already had it as its name.
+#[derive(Debug, Default, Serialize)]
+pub struct KanjiNamesReport {
+    pub kanjidic2_version: String,
+    pub collisions: Vec<NameCollision>,
+}
+
+/// A kanji whose first meaning was already the name of another kanji.
+#[derive(Debug, Serialize)]
+pub struct NameCollision {
+    pub id: KanjiId,
+    pub kanji: String,
+    /// The first meaning of the kanji.
+    pub meaning: String,
+    /// The kanji that has the meaning as its name.
+    pub named_kanji: String,
+    /// The later meaning the kanji was named after, or `None` if all of its meanings were taken.
+    pub name: Option<String>,
+}
+
+/// The changes made by a wordfile skeleton update.
+#[derive(Debug, Default, Serialize)]
+pub struct WordfileReport {
+    pub old_jmdict_version: String,
+    pub new_jmdict_version: String,
+    pub added: Vec<AddedWord>,
+    pub removed: Vec<RemovedWord>,
+    pub reassigned: Vec<ReassignedWord>,
+}
+
+/// A word added to the wordfile skeleton with a new id.
+#[derive(Debug, Serialize)]
+pub struct AddedWord {
+    pub id: WordId,
+    pub jmdict_id: Option<JmdictSeq>,
+    pub written_forms: Vec<String>,
+}
+
+/// A word removed from the wordfile skeleton.
+#[derive(Debug, Serialize)]
+pub struct RemovedWord {
+    pub id: WordId,
+    pub jmdict_id: Option<JmdictSeq>,
+    pub written_forms: Vec<String>,
+    pub successor_id: Option<WordId>,
+}
+
+/// An existing id carried forward to a word with a different written form or JMdict entry.
+#[derive(Debug, Serialize)]
+pub struct ReassignedWord {
+    pub id: WordId,
+    pub reason: Reassignment,
+    pub old_jmdict_id: Option<JmdictSeq>,
+    pub new_jmdict_id: Option<JmdictSeq>,
+    pub old_written_forms: Vec<String>,
+    pub new_written_forms: Vec<String>,
+}
+
+/// The reason an id was carried forward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reassignment {
+    /// A manual override.
+    Override,
+    /// The written form moved to a different JMdict entry.
+    Moved,
+    /// The written form was edited within the same JMdict entry.
+    Renamed,
+}
+
+impl Report for KanjifileReport {
+    fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        heading(
+            &mut md,
+            "Kanjifile skeleton update",
+            "KANJIDIC2",
+            &self.old_kanjidic2_version,
+            &self.new_kanjidic2_version,
+        );
+        table(
+            &mut md,
+            "Added kanji",
+            &["id", "kanji", "source"],
+            self.added.iter().map(|k| {
+                let source = match k.source {
+                    KanjiSource::Kanjidic2 => "KANJIDIC2",
+                    KanjiSource::Jmdict => "JMdict",
+                };
+                vec![k.id.to_string(), k.kanji.clone(), source.to_string()]
+            }),
+        );
+        md
+    }
+}
+
+impl Report for KanjiNamesReport {
+    fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        heading(
+            &mut md,
+            "Kanji name collisions",
+            "KANJIDIC2",
+            &self.kanjidic2_version,
+            &self.kanjidic2_version,
+        );
+        let (renamed, unnamed): (Vec<_>, Vec<_>) =
+            self.collisions.iter().partition(|c| c.name.is_some());
+        table(
+            &mut md,
+            "Named after a later meaning",
+            &["id", "kanji", "first meaning", "named kanji", "name"],
+            renamed.iter().map(|c| {
+                vec![
+                    c.id.to_string(),
+                    c.kanji.clone(),
+                    c.meaning.clone(),
+                    c.named_kanji.clone(),
+                    optional(c.name.as_deref()),
+                ]
+            }),
+        );
+        table(
+            &mut md,
+            "Left unnamed",
+            &["id", "kanji", "first meaning", "named kanji"],
+            unnamed.iter().map(|c| {
+                vec![
+                    c.id.to_string(),
+                    c.kanji.clone(),
+                    c.meaning.clone(),
+                    c.named_kanji.clone(),
+                ]
+            }),
+        );
+        md
+    }
+}
+
+impl Report for WordfileReport {
+    fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        heading(
+            &mut md,
+            "Wordfile skeleton update",
+            "JMdict",
+            &self.old_jmdict_version,
+            &self.new_jmdict_version,
+        );
+        table(
+            &mut md,
+            "Added words",
+            &["id", "JMdict id", "written forms"],
+            self.added.iter().map(|w| {
+                vec![
+                    w.id.to_string(),
+                    optional(w.jmdict_id),
+                    w.written_forms.join(", "),
+                ]
+            }),
+        );
+        table(
+            &mut md,
+            "Removed words",
+            &["id", "JMdict id", "written forms", "successor"],
+            self.removed.iter().map(|w| {
+                vec![
+                    w.id.to_string(),
+                    optional(w.jmdict_id),
+                    w.written_forms.join(", "),
+                    optional(w.successor_id),
+                ]
+            }),
+        );
+        table(
+            &mut md,
+            "Reassigned ids",
+            &[
+                "id",
+                "reason",
+                "old JMdict id",
+                "new JMdict id",
+                "old written forms",
+                "new written forms",
+            ],
+            self.reassigned.iter().map(|w| {
+                let reason = match w.reason {
+                    Reassignment::Override => "override",
+                    Reassignment::Moved => "moved",
+                    Reassignment::Renamed => "renamed",
+                };
+                vec![
+                    w.id.to_string(),
+                    reason.to_string(),
+                    optional(w.old_jmdict_id),
+                    optional(w.new_jmdict_id),
+                    w.old_written_forms.join(", "),
+                    w.new_written_forms.join(", "),
+                ]
+            }),
+        );
+        md
+    }
+}
+
+/// Writes the report to the given path, as Markdown if the path ends in `.md` and as JSON otherwise.
+/// The directory is created if needed.
+pub fn write(report: &impl Report, path: &Path) -> eyre::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let contents = if path.extension().is_some_and(|e| e == "md") {
+        report.to_markdown()
+    } else {
+        serde_json::to_string_pretty(report)?
+    };
+    fs::write(path, contents)
+        .wrap_err_with(|| format!("Failed to write report at '{}'", path.display()))?;
+    Ok(())
+}
+
+fn heading(md: &mut String, title: &str, source: &str, old: &str, new: &str) {
+    let _ = writeln!(md, "# {title}\n");
+    if old == new {
+        let _ = writeln!(md, "{source} version: {new}\n");
+    } else {
+        let _ = writeln!(md, "{source} version: {old} → {new}\n");
+    }
+}
+
+fn table(
+    md: &mut String,
+    title: &str,
+    columns: &[&str],
+    rows: impl ExactSizeIterator<Item = Vec<String>>,
+) {
+    let _ = writeln!(md, "## {title} ({})\n", rows.len());
+    if rows.len() == 0 {
+        let _ = writeln!(md, "None.\n");
+        return;
+    }
+    let _ = writeln!(md, "| {} |", columns.join(" | "));
+    let _ = writeln!(md, "|{}", " --- |".repeat(columns.len()));
+    for row in rows {
+        // pipes would end the cell early
+        let row = row
+            .iter()
+            .map(|cell| cell.replace('|', "\\|"))
+            .collect::<Vec<_>>();
+        let _ = writeln!(md, "| {} |", row.join(" | "));
+    }
+    md.push('\n');
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn strings(strings: &[&str]) -> Vec<String> {
+        strings.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn wordfile_report() -> WordfileReport {
+        WordfileReport {
+            old_jmdict_version: "1.09".to_string(),
+            new_jmdict_version: "1.10".to_string(),
+            added: vec![AddedWord {
+                id: WordId(4),
+                jmdict_id: Some(JmdictSeq(1000040)),
+                written_forms: strings(&["書"]),
+            }],
+            removed: vec![RemovedWord {
+                id: WordId(2),
+                jmdict_id: None,
+                written_forms: strings(&["a|b"]),
+                successor_id: Some(WordId(1)),
+            }],
+            reassigned: vec![ReassignedWord {
+                id: WordId(1),
+                reason: Reassignment::Moved,
+                old_jmdict_id: Some(JmdictSeq(1000010)),
+                new_jmdict_id: Some(JmdictSeq(1000030)),
+                old_written_forms: strings(&["船"]),
+                new_written_forms: strings(&["船"]),
+            }],
+        }
+    }
+
+    #[test]
+    fn wordfile_report_json() {
+        assert_eq!(
+            serde_json::to_value(wordfile_report()).unwrap(),
+            json!({
+                "old_jmdict_version": "1.09",
+                "new_jmdict_version": "1.10",
+                "added": [{ "id": 4, "jmdict_id": 1000040, "written_forms": ["書"] }],
+                "removed": [{
+                    "id": 2,
+                    "jmdict_id": null,
+                    "written_forms": ["a|b"],
+                    "successor_id": 1,
+                }],
+                "reassigned": [{
+                    "id": 1,
+                    "reason": "moved",
+                    "old_jmdict_id": 1000010,
+                    "new_jmdict_id": 1000030,
+                    "old_written_forms": ["船"],
+                    "new_written_forms": ["船"],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn wordfile_report_markdown() {
+        assert_eq!(
+            wordfile_report().to_markdown(),
+            "\
+# Wordfile skeleton update
+
+JMdict version: 1.09 → 1.10
+
+## Added words (1)
+
+| id | JMdict id | written forms |
+| --- | --- | --- |
+| 4 | 1000040 | 書 |
+
+## Removed words (1)
+
+| id | JMdict id | written forms | successor |
+| --- | --- | --- | --- |
+| 2 |  | a\\|b | 1 |
+
+## Reassigned ids (1)
+
+| id | reason | old JMdict id | new JMdict id | old written forms | new written forms |
+| --- | --- | --- | --- | --- | --- |
+| 1 | moved | 1000010 | 1000030 | 船 | 船 |
+
+"
+        );
+    }
+
+    #[test]
+    fn kanjifile_report_markdown() {
+        let report = KanjifileReport {
+            old_kanjidic2_version: "2023-335".to_string(),
+            new_kanjidic2_version: "2023-335".to_string(),
+            added: vec![],
+        };
+        assert_eq!(
+            report.to_markdown(),
+            "\
+# Kanjifile skeleton update
+
+KANJIDIC2 version: 2023-335
+
+## Added kanji (0)
+
+None.
+
+"
+        );
+    }
+
+    #[test]
+    fn kanji_names_report_markdown() {
+        let report = KanjiNamesReport {
+            kanjidic2_version: "2023-335".to_string(),
+            collisions: vec![
+                NameCollision {
+                    id: KanjiId(2),
+                    kanji: "陽".to_string(),
+                    meaning: "sun".to_string(),
+                    named_kanji: "日".to_string(),
+                    name: Some("sunshine".to_string()),
+                },
+                NameCollision {
+                    id: KanjiId(3),
+                    kanji: "曰".to_string(),
+                    meaning: "say".to_string(),
+                    named_kanji: "言".to_string(),
+                    name: None,
+                },
+            ],
+        };
+        assert_eq!(
+            report.to_markdown(),
+            "\
+# Kanji name collisions
+
+KANJIDIC2 version: 2023-335
+
+## Named after a later meaning (1)
+
+| id | kanji | first meaning | named kanji | name |
+| --- | --- | --- | --- | --- |
+| 2 | 陽 | sun | 日 | sunshine |
+
+## Left unnamed (1)
+
+| id | kanji | first meaning | named kanji |
+| --- | --- | --- | --- |
+| 3 | 曰 | say | 言 |
+
+"
+        );
+    }
+
+    #[test]
+    fn format_is_chosen_by_extension() {
+        let dir = std::env::temp_dir().join(format!("jadata-report-{}", std::process::id()));
+        let report = wordfile_report();
+        write(&report, &dir.join("report.md")).unwrap();
+        write(&report, &dir.join("report.json")).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("report.md")).unwrap(),
+            report.to_markdown()
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+        assert_eq!(json, serde_json::to_value(&report).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! Writes the generated files in each of the output formats.
+//! A new output file only needs to implement `OutputFile`,
+//! and a new format a method in `OutputFile` with a default implementation and a branch in `write` and `read`.
+
+use super::{compression::Encoder, OutputOptions};
+use crate::{cli::Format, input};
+use eyre::WrapErr;
+use jadata::{
+    kanjifile::{legacy::LegacyKanjifile, Kanjifile},
+    wordfile::{
+        legacy::{LegacyHeader, LegacyWordfile},
+        stream::{JsonWriter, PostcardWriter},
+        Wordfile,
+    },
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+/// A file generated by the CLI.
+/// The default implementations serialize the whole file at once.
+pub trait OutputFile: Serialize + DeserializeOwned {
+    /// Writes the file as JSON, without any whitespace if `compact` is set.
+    fn write_json(&self, writer: &mut dyn Write, compact: bool) -> eyre::Result<()> {
+        if compact {
+            serde_json::to_writer(writer, self)?;
+        } else {
+            serde_json::to_writer_pretty(writer, self)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the file as postcard.
+    fn write_postcard(&self, writer: &mut dyn Write) -> eyre::Result<()> {
+        let serialized = postcard::to_stdvec(self)?;
+        writer.write_all(&serialized)?;
+        Ok(())
+    }
+
+    /// Writes the file in the postcard layout of older versions of jadata.
+    fn write_legacy_postcard(&self, _writer: &mut dyn Write) -> eyre::Result<()> {
+        eyre::bail!("The file has no legacy postcard layout")
+    }
+
+    /// Reads the file from the postcard layout of older versions of jadata.
+    fn read_legacy_postcard(_bytes: &[u8]) -> eyre::Result<Self> {
+        eyre::bail!("The file has no legacy postcard layout")
+    }
+}
+
+impl OutputFile for Kanjifile {
+    fn write_legacy_postcard(&self, writer: &mut dyn Write) -> eyre::Result<()> {
+        let legacy = LegacyKanjifile::try_from(self.clone())?;
+        let serialized = postcard::to_stdvec(&legacy)?;
+        writer.write_all(&serialized)?;
+        Ok(())
+    }
+
+    fn read_legacy_postcard(bytes: &[u8]) -> eyre::Result<Self> {
+        let legacy: LegacyKanjifile = postcard::from_bytes(bytes)?;
+        Ok(legacy.into())
+    }
+}
+
+// the wordfile is written one word at a time to avoid holding the whole serialized file in memory
+impl OutputFile for Wordfile {
+    fn write_json(&self, writer: &mut dyn Write, compact: bool) -> eyre::Result<()> {
+        let mut writer = if compact {
+            JsonWriter::compact(writer, &self.header)?
+        } else {
+            JsonWriter::new(writer, &self.header)?
+        };
+        for word in &self.words {
+            writer.write(word)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn write_postcard(&self, writer: &mut dyn Write) -> eyre::Result<()> {
+        let mut writer = PostcardWriter::new(writer, &self.header, self.words.len())?;
+        for word in &self.words {
+            writer.write(word)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn write_legacy_postcard(&self, writer: &mut dyn Write) -> eyre::Result<()> {
+        let header = LegacyHeader::from(&self.header);
+        let mut writer = PostcardWriter::legacy(writer, &header, self.words.len())?;
+        for word in &self.words {
+            writer.write(word)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn read_legacy_postcard(bytes: &[u8]) -> eyre::Result<Self> {
+        let legacy: LegacyWordfile = postcard::from_bytes(bytes)?;
+        Ok(legacy.into())
+    }
+}
+
+/// Writes the file to the given path with the given options, creating the directory if needed.
+pub fn write<T: OutputFile>(file: &T, path: &Path, options: OutputOptions) -> eyre::Result<()> {
+    let mut writer = create(path, options)?;
+    match options.format {
+        Format::Json => file.write_json(&mut writer, options.compact)?,
+        Format::Postcard => file.write_postcard(&mut writer)?,
+        Format::LegacyPostcard => file.write_legacy_postcard(&mut writer)?,
+    }
+    writer.finish()?.flush()?;
+    Ok(())
+}
+
+/// Writes a skeleton to the given path. Skeletons are always uncompressed pretty-printed JSON
+/// so that they can be edited by hand and diffed in version control.
+pub fn write_skeleton<T: OutputFile>(skeleton: &T, path: &Path) -> eyre::Result<()> {
+    let options = OutputOptions {
+        format: Format::Json,
+        compression: None,
+        compact: false,
+    };
+    write(skeleton, path, options)
+}
+
+/// Reads a file written by `write` in the given format. Compressed files are decompressed transparently.
+pub fn read<T: OutputFile>(path: &Path, format: Format) -> eyre::Result<T> {
+    let mut reader = input::open(path)?;
+    let value = match format {
+        Format::Json => serde_json::from_reader(reader)?,
+        Format::Postcard => {
+            let mut buf = vec![];
+            reader.read_to_end(&mut buf)?;
+            match postcard::from_bytes(&buf) {
+                Ok(value) => value,
+                // postcard is not self-describing, so a file in an older layout fails to decode
+                Err(_) if T::read_legacy_postcard(&buf).is_ok() => eyre::bail!(
+                    "'{}' has the postcard layout of an older version of jadata, read it with the legacy-postcard format",
+                    path.display()
+                ),
+                Err(e) => {
+                    return Err(e).wrap_err_with(|| {
+                        format!(
+                            "Failed to decode '{}' as postcard, files written by older versions of jadata need to be generated again",
+                            path.display()
+                        )
+                    })
+                }
+            }
+        }
+        Format::LegacyPostcard => {
+            let mut buf = vec![];
+            reader.read_to_end(&mut buf)?;
+            T::read_legacy_postcard(&buf)?
+        }
+    };
+    Ok(value)
+}
+
+// creates the file and its directory for buffered writing, compressing it if requested
+fn create(path: &Path, options: OutputOptions) -> eyre::Result<Encoder<BufWriter<File>>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = File::create(path)
+        .wrap_err_with(|| format!("Failed to create file at '{}'", path.display()))?;
+    let encoder = Encoder::new(BufWriter::new(file), options.compression)?;
+    Ok(encoder)
+}
@@ -1,49 +1,78 @@
-use crate::input::{
-    jmdict::{JMdict, REle, Sense},
-    jmdict_furigana,
+use crate::{
+    config::Filters,
+    error::{Error, Errors},
+    input::{
+        jmdict::{self, Entry, REle, Sense},
+        jmdict_furigana,
+    },
+    parallel,
+};
+use jadata::{
+    id::JmdictSeq,
+    wordfile::{Furigana, Reading, Word, Wordfile},
 };
-use eyre::Context;
-use jadata::wordfile::{Furigana, Reading, Word, Wordfile};
 use std::collections::HashMap;
 
 /// Fills the wordfile skeleton with data.
+/// Entries that cannot be filled in are skipped and recorded in `errors`.
 pub fn fill_skeleton(
     skeleton: &mut Wordfile,
     version: String,
-    jmdict: JMdict,
-    jmdict_version: String,
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+    jmdict_header: jmdict::Header,
     furigana: Vec<jmdict_furigana::Furigana>,
+    filters: &Filters,
+    errors: &mut Errors,
 ) -> eyre::Result<()> {
     let furigana = process_furigana(furigana);
-    let mut skeleton_map: HashMap<u32, Vec<&mut Word>> = HashMap::new();
+    let mut skeleton_map: HashMap<JmdictSeq, Vec<&mut Word>> = HashMap::new();
     for word in skeleton.words.iter_mut() {
         if let Some(jmdict_id) = word.jmdict_id {
             let entry = skeleton_map.entry(jmdict_id).or_default();
             entry.push(word);
         }
     }
-    let jmdict_words = process_jmdict(jmdict, &furigana)?;
-    for jmdict_word in jmdict_words {
-        let jmdict_id = jmdict_word.jmdict_id;
-        let words = skeleton_map
-            .get_mut(&jmdict_id)
-            .unwrap_or_else(|| panic!("missing jmdict_id {jmdict_id}"));
-        for word in words {
-            if !word.written_forms.contains(&jmdict_word.written_form) {
-                continue;
-            }
-            word.meanings = jmdict_word.meanings.clone();
-            if let Some(reading) = &jmdict_word.reading {
-                word.readings.push(Reading {
-                    furigana: jmdict_word.furigana.clone(),
-                    reading: reading.clone(),
-                    usually_kana: jmdict_word.usually_kana,
-                });
+    parallel::map_entries(
+        jmdict,
+        |entry| match entry.ent_seq.parse::<JmdictSeq>() {
+            Ok(jmdict_id) => Ok((jmdict_id, process_entry(entry, &furigana, filters))),
+            Err(_) => Err(entry.ent_seq),
+        },
+        |processed| {
+            let (jmdict_id, jmdict_words) = match processed {
+                Ok(processed) => processed,
+                Err(ent_seq) => {
+                    errors.push(Error::InvalidEntSeq { ent_seq });
+                    return;
+                }
+            };
+            let words = match skeleton_map.get_mut(&jmdict_id) {
+                Some(words) => words,
+                None => {
+                    errors.push(Error::MissingJmdictId { jmdict_id });
+                    return;
+                }
+            };
+            for jmdict_word in jmdict_words {
+                for word in words.iter_mut() {
+                    if !word.written_forms.contains(&jmdict_word.written_form) {
+                        continue;
+                    }
+                    word.meanings = jmdict_word.meanings.clone();
+                    if let Some(reading) = &jmdict_word.reading {
+                        word.readings.push(Reading {
+                            furigana: jmdict_word.furigana.clone(),
+                            reading: reading.clone(),
+                            usually_kana: jmdict_word.usually_kana,
+                        });
+                    }
+                }
             }
-        }
-    }
+        },
+    )?;
     skeleton.header.version = version;
-    skeleton.header.jmdict_version = jmdict_version;
+    skeleton.header.jmdict_version = jmdict_header.revision;
+    skeleton.header.jmdict_date_of_creation = jmdict_header.date_of_creation;
     Ok(())
 }
 
@@ -73,53 +102,52 @@ fn process_furigana(
         .collect()
 }
 
-fn process_jmdict(
-    jmdict: JMdict,
+// turns a jmdict entry into a list of words, one for each combination of written form and reading
+fn process_entry(
+    entry: Entry,
     furigana: &HashMap<(String, String), Vec<Furigana>>,
-) -> eyre::Result<Vec<JMdictWord>> {
+    filters: &Filters,
+) -> Vec<JMdictWord> {
     let mut tuples = vec![];
-    for entry in jmdict.entry {
-        let jmdict_id = entry.ent_seq.parse().wrap_err("invalid id")?;
-        if entry.k_ele.is_empty() {
+    if entry.k_ele.is_empty() {
+        for rele in &entry.r_ele {
+            tuples.push(process_jmdict_word(
+                furigana,
+                &entry.sense,
+                None,
+                rele,
+                false,
+                filters,
+            ));
+        }
+    } else {
+        for kele in entry.k_ele {
+            let rare_written_form = kele.ke_inf.iter().any(|e| e.code == "rK");
+            let keb = kele.keb;
             for rele in &entry.r_ele {
-                tuples.push(process_jmdict_word(
-                    jmdict_id,
-                    furigana,
-                    &entry.sense,
-                    None,
-                    rele,
-                    false,
-                ));
-            }
-        } else {
-            for kele in entry.k_ele {
-                let rare_written_form = kele.ke_inf.iter().any(|s| s == "rarely-used kanji form");
-                let keb = kele.keb;
-                for rele in &entry.r_ele {
-                    if rele.re_restr.is_empty() || rele.re_restr.contains(&keb) {
-                        tuples.push(process_jmdict_word(
-                            jmdict_id,
-                            furigana,
-                            &entry.sense,
-                            Some(keb.clone()),
-                            rele,
-                            rare_written_form,
-                        ));
-                    }
+                if rele.re_restr.is_empty() || rele.re_restr.contains(&keb) {
+                    tuples.push(process_jmdict_word(
+                        furigana,
+                        &entry.sense,
+                        Some(keb.clone()),
+                        rele,
+                        rare_written_form,
+                        filters,
+                    ));
                 }
             }
         }
     }
-    Ok(tuples)
+    tuples
 }
 
 fn process_jmdict_word(
-    jmdict_id: u32,
     furigana: &HashMap<(String, String), Vec<Furigana>>,
     sense: &[Sense],
     keb: Option<String>,
     rele: &REle,
     rare_written_form: bool,
+    filters: &Filters,
 ) -> JMdictWord {
     let reb = rele.reb.clone();
     let keb = keb.unwrap_or_else(|| reb.clone());
@@ -128,17 +156,15 @@ fn process_jmdict_word(
     let mut usually_kana = rare_written_form;
     let mut meanings = vec![];
     for s in sense {
-        if s.misc
-            .iter()
-            .any(|m| m == "word usually written using kana alone")
-        {
+        if s.misc.iter().any(|m| m.code == "uk") {
             usually_kana = true;
         }
         let stagk = s.stagk.is_empty() || s.stagk.contains(&keb);
         let stagr = s.stagr.is_empty() || s.stagr.contains(&reb);
-        if stagk && stagr {
+        let included = filters.includes_sense(s.misc.iter().map(|m| m.code.as_str()));
+        if stagk && stagr && included {
             for g in s.gloss.iter().filter_map(|g| {
-                if g.lang.is_none() {
+                if filters.includes_language(g.lang.as_deref()) {
                     Some(g.value.clone())
                 } else {
                     None
@@ -149,7 +175,6 @@ fn process_jmdict_word(
         }
     }
     JMdictWord {
-        jmdict_id,
         written_form: keb.clone(),
         reading: if keb == reb { None } else { Some(reb) },
         furigana,
@@ -160,7 +185,6 @@ fn process_jmdict_word(
 
 #[derive(Debug)]
 struct JMdictWord {
-    jmdict_id: u32,
     written_form: String,
     reading: Option<String>,
     furigana: Vec<Furigana>,
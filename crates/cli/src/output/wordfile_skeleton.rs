@@ -1,50 +1,122 @@
-use crate::input::jmdict::{JMdict, Sense};
-use jadata::wordfile::{Header, Word, Wordfile};
-use std::collections::{HashMap, HashSet};
+use crate::{
+    error::{Error, Errors},
+    input::jmdict::{self, Entry},
+    output::report::{AddedWord, ReassignedWord, RemovedWord, WordfileReport},
+    parallel,
+};
+use eyre::ContextCompat;
+use jadata::{
+    id::{JmdictSeq, WordId},
+    wordfile::{Header, Reading, Tombstone, Word, Wordfile},
+};
+use serde::{de, Deserialize, Deserializer};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use successor::{NewWord, SuccessorOverride};
 use wana_kana::ConvertJapanese;
 
+pub mod successor;
+
+/// A manually curated word that is not in JMdict.
+/// Unlike the words from JMdict, curated words carry their own data in the skeleton.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CuratedWord {
+    #[serde(deserialize_with = "non_empty")]
+    pub written_forms: Vec<String>,
+    #[serde(default)]
+    pub meanings: Vec<String>,
+    #[serde(default)]
+    pub readings: Vec<Reading>,
+}
+
+// a word without written forms can neither be matched to the skeleton nor pass validation
+fn non_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let written_forms = Vec::<String>::deserialize(deserializer)?;
+    if written_forms.is_empty() {
+        return Err(de::Error::invalid_length(0, &"at least one written form"));
+    }
+    Ok(written_forms)
+}
+
 /// Creates the kanjifile skeleton that only contains the bare minimum amount of data.
-pub fn create(jmdict: JMdict, jmdict_version: String) -> eyre::Result<Wordfile> {
+/// Entries that cannot be processed are skipped and recorded in `errors`.
+pub fn create(
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+    jmdict_header: jmdict::Header,
+    curated_words: &[CuratedWord],
+    errors: &mut Errors,
+) -> eyre::Result<(Wordfile, WordfileReport)> {
     let mut skeleton = Wordfile {
         header: Header {
             version: "".to_string(),
             jmdict_version: "".to_string(),
-            last_word_id: 0,
+            jmdict_date_of_creation: "".to_string(),
+            last_word_id: WordId::default(),
+            tombstones: Vec::new(),
         },
         words: Vec::new(),
     };
-    update(&mut skeleton, jmdict, jmdict_version)?;
-    Ok(skeleton)
+    let report = update(
+        &mut skeleton,
+        jmdict,
+        jmdict_header,
+        &[],
+        curated_words,
+        errors,
+    )?;
+    Ok((skeleton, report))
 }
 
-pub fn update(wordfile: &mut Wordfile, jmdict: JMdict, jmdict_version: String) -> eyre::Result<()> {
+/// Updates a wordfile skeleton with new words from JMdict and the curated words, and returns a report of the changes.
+/// Words whose written form was changed keep their ids, see [`successor`].
+/// Curated words already in the skeleton are matched by their written forms and replaced, and words without a JMdict id
+/// are otherwise left as they are.
+/// Entries that cannot be processed are skipped and recorded in `errors`, except for entries with an invalid `ent_seq`
+/// when the skeleton already has words from JMdict. Their words cannot be told apart from removed ones,
+/// so skipping the entries would turn the words into tombstones for good, and the update fails instead.
+pub fn update(
+    wordfile: &mut Wordfile,
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+    jmdict_header: jmdict::Header,
+    successor_overrides: &[SuccessorOverride],
+    curated_words: &[CuratedWord],
+    errors: &mut Errors,
+) -> eyre::Result<WordfileReport> {
+    let mut report = WordfileReport {
+        old_jmdict_version: wordfile.header.jmdict_version.clone(),
+        new_jmdict_version: jmdict_header.revision.clone(),
+        ..WordfileReport::default()
+    };
     // a jmdict entry can correspond to multiple jadata entries
     // a jmdict id + a written form converted to katakana corresponds to a single jadata entry
-    let existing_words = std::mem::take(&mut wordfile.words);
-    let mut existing_words = existing_words
+    // no need to update words that aren't in JMdict
+    let existing_words_keys = wordfile
+        .words
         .iter()
-        .flat_map(|w| {
-            // no need to update words that aren't in JMdict
-            let jmdict_id = if let Some(jmdict_id) = w.jmdict_id {
-                jmdict_id
-            } else {
-                return None;
-            };
-            let key = JMdictWordKatakana {
-                jmdict_id,
-                // all written forms in a single jadata entry are equivalent when converted to katakana, so we can just pick one
-                written_form_katakana: w.written_forms[0].to_katakana(),
-            };
-            let val = w;
-            Some((key, val))
-        })
-        .collect::<HashMap<_, _>>();
+        .filter_map(JMdictWordKatakana::from_word)
+        .collect::<HashSet<_>>();
 
-    let jmdict_words = process_jmdict(jmdict);
+    let (jmdict_words, invalid_ent_seqs) = process_jmdict(jmdict)?;
+    let mut skipped = Errors::new();
+    for ent_seq in invalid_ent_seqs {
+        skipped.push(Error::InvalidEntSeq { ent_seq });
+    }
+    // the words of the skipped entries cannot be told apart from removed words,
+    // so going on would turn them into tombstones for good
+    if !skipped.is_empty() && !existing_words_keys.is_empty() {
+        return Err(eyre::Report::new(skipped).wrap_err(
+            "Cannot update the wordfile skeleton without removing the words of the skipped entries",
+        ));
+    }
+    errors.extend(skipped);
     let mut katana_to_verbatim: HashMap<JMdictWordKatakana, Vec<JMdictWordVerbatim>> =
         HashMap::new();
-    for jmdict_word in jmdict_words {
-        let jadata_word = JMdictWordKatakana::from_verbatim(&jmdict_word);
+    let mut shared_readings: HashMap<JmdictSeq, BTreeSet<String>> = HashMap::new();
+    for (jadata_word, jmdict_word) in jmdict_words {
+        shared_readings
+            .entry(jmdict_word.id)
+            .and_modify(|r| r.retain(|reading| jmdict_word.readings.contains(reading)))
+            .or_insert_with(|| jmdict_word.readings.clone());
         let entry = katana_to_verbatim.entry(jadata_word).or_default();
         entry.push(jmdict_word);
     }
@@ -52,14 +124,15 @@ pub fn update(wordfile: &mut Wordfile, jmdict: JMdict, jmdict_version: String) -
     // here, we remove words that no longer exist
     // while JMdict entries should not disappear, a word may possibly have a written form changed/removed
     // we could leave them in, but we're treating JMdict as the authority here
-    let existing_words_keys = existing_words.keys().cloned().collect::<HashSet<_>>();
-    let jmdict_words_keys = katana_to_verbatim.keys().cloned().collect::<HashSet<_>>();
     // removed words are ones that exist in the skeleton currently but no longer found in JMdict
-    let removed_words_keys = existing_words_keys.difference(&jmdict_words_keys);
-    for removed_word_key in removed_words_keys {
-        let removed = existing_words.remove(removed_word_key).unwrap();
-        tracing::warn!("removing word {removed:#?}");
-    }
+    // they are kept as tombstones so that data referring to them can be migrated
+    let (words, removed_words): (Vec<_>, Vec<_>) = std::mem::take(&mut wordfile.words)
+        .into_iter()
+        .partition(|w| match JMdictWordKatakana::from_word(w) {
+            Some(key) => katana_to_verbatim.contains_key(&key),
+            None => true,
+        });
+    wordfile.words = words;
 
     // then, we add in the new words
     // new words are ones that exist in JMdict but not in the skeleton
@@ -70,46 +143,186 @@ pub fn update(wordfile: &mut Wordfile, jmdict: JMdict, jmdict_version: String) -
             .cmp(&b.0.written_form_katakana)
             .then(a.0.jmdict_id.cmp(&b.0.jmdict_id))
     });
-    let last_word_id = &mut wordfile.header.last_word_id;
-    let new_words = pairs
+    let pairs = pairs
         .into_iter()
         .map(|(ja, jm)| {
             let mut written_forms = HashSet::new();
+            let mut readings = BTreeSet::new();
             for jm in jm {
-                written_forms.insert(jm.written_form.clone());
+                written_forms.insert(jm.written_form);
+                readings.extend(jm.readings);
             }
             let mut written_forms = written_forms.into_iter().collect::<Vec<_>>();
             written_forms.sort();
-            *last_word_id += 1;
-            Word {
-                id: *last_word_id,
-                jmdict_id: Some(ja.jmdict_id),
+            (ja.jmdict_id, written_forms, readings)
+        })
+        .collect::<Vec<_>>();
+
+    // some of the new words may be removed words with a changed written form or JMdict entry
+    let new_words = pairs
+        .iter()
+        .map(|(jmdict_id, written_forms, readings)| NewWord {
+            jmdict_id: *jmdict_id,
+            written_forms,
+            readings,
+        })
+        .collect::<Vec<_>>();
+    let successors = successor::detect(
+        &removed_words,
+        &new_words,
+        &wordfile.words,
+        &shared_readings,
+        successor_overrides,
+        errors,
+    );
+    let mut carried_words = HashMap::new();
+
+    // removed words that were not carried forward are kept as tombstones
+    for removed in removed_words {
+        if successors.carried.values().any(|(id, _)| *id == removed.id) {
+            carried_words.insert(removed.id, removed);
+            continue;
+        }
+        tracing::warn!("removing word {removed:#?}");
+        let successor_id = successors.existing.get(&removed.id).copied();
+        report.removed.push(RemovedWord {
+            id: removed.id,
+            jmdict_id: removed.jmdict_id,
+            written_forms: removed.written_forms.clone(),
+            successor_id,
+        });
+        wordfile.header.tombstones.push(Tombstone {
+            id: removed.id,
+            jmdict_id: removed.jmdict_id,
+            written_forms: removed.written_forms,
+            removed_in_jmdict_version: jmdict_header.revision.clone(),
+            successor_id,
+        });
+    }
+
+    // the header may be behind if words were added to the skeleton by hand
+    let mut last_word_id = wordfile
+        .words
+        .iter()
+        .map(|w| w.id)
+        .chain(wordfile.header.tombstones.iter().map(|t| t.id))
+        .max()
+        .unwrap_or_default()
+        .max(wordfile.header.last_word_id);
+    let mut new_words = pairs
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (jmdict_id, written_forms, _))| {
+            let id = match successors.carried.get(&idx) {
+                Some((id, reason)) => {
+                    let old = &carried_words[id];
+                    report.reassigned.push(ReassignedWord {
+                        id: *id,
+                        reason: *reason,
+                        old_jmdict_id: old.jmdict_id,
+                        new_jmdict_id: Some(jmdict_id),
+                        old_written_forms: old.written_forms.clone(),
+                        new_written_forms: written_forms.clone(),
+                    });
+                    *id
+                }
+                None => {
+                    last_word_id = last_word_id.next().wrap_err_with(|| {
+                        format!("Ran out of word ids after {last_word_id} while adding {written_forms:?}")
+                    })?;
+                    report.added.push(AddedWord {
+                        id: last_word_id,
+                        jmdict_id: Some(jmdict_id),
+                        written_forms: written_forms.clone(),
+                    });
+                    last_word_id
+                }
+            };
+            Ok(Word {
+                id,
+                jmdict_id: Some(jmdict_id),
                 written_forms,
                 meanings: vec![],
                 readings: vec![],
-            }
+            })
         })
-        .collect::<Vec<_>>();
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    // curated words are not in JMdict, so they are matched to the existing ones by their written forms instead
+    for curated in curated_words {
+        let existing = wordfile.words.iter_mut().find(|w| {
+            w.jmdict_id.is_none()
+                && w.written_forms
+                    .iter()
+                    .any(|wf| curated.written_forms.contains(wf))
+        });
+        match existing {
+            Some(word) => {
+                word.written_forms = curated.written_forms.clone();
+                word.meanings = curated.meanings.clone();
+                word.readings = curated.readings.clone();
+            }
+            None => {
+                last_word_id = last_word_id.next().wrap_err_with(|| {
+                    format!(
+                        "Ran out of word ids after {last_word_id} while adding curated word {:?}",
+                        curated.written_forms
+                    )
+                })?;
+                tracing::info!(
+                    "adding curated word {last_word_id} {:?}",
+                    curated.written_forms
+                );
+                report.added.push(AddedWord {
+                    id: last_word_id,
+                    jmdict_id: None,
+                    written_forms: curated.written_forms.clone(),
+                });
+                new_words.push(Word {
+                    id: last_word_id,
+                    jmdict_id: None,
+                    written_forms: curated.written_forms.clone(),
+                    meanings: curated.meanings.clone(),
+                    readings: curated.readings.clone(),
+                });
+            }
+        }
+    }
 
-    wordfile.header.jmdict_version = jmdict_version;
+    wordfile.header.last_word_id = last_word_id;
+    wordfile.header.jmdict_version = jmdict_header.revision;
+    wordfile.header.jmdict_date_of_creation = jmdict_header.date_of_creation;
     wordfile.words.extend(new_words);
+    // carried forward words keep their place in the skeleton
+    wordfile.words.sort_by_key(|w| w.id);
+    report.reassigned.sort_by_key(|w| w.id);
 
-    Ok(())
+    Ok(report)
 }
 
 #[derive(Debug)]
 struct JMdictWordVerbatim {
-    id: u32,
+    id: JmdictSeq,
     written_form: String,
+    readings: BTreeSet<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct JMdictWordKatakana {
-    jmdict_id: u32,
+    jmdict_id: JmdictSeq,
     written_form_katakana: String,
 }
 
 impl JMdictWordKatakana {
+    // the key of a word in the skeleton, if it is from JMdict
+    fn from_word(word: &Word) -> Option<Self> {
+        Some(Self {
+            jmdict_id: word.jmdict_id?,
+            // all written forms in a single jadata entry are equivalent when converted to katakana, so we can just pick one
+            written_form_katakana: word.written_forms[0].to_katakana(),
+        })
+    }
+
     fn from_verbatim(key: &JMdictWordVerbatim) -> Self {
         let written_form_katakana = key.written_form.to_katakana();
         Self {
@@ -119,64 +332,287 @@ impl JMdictWordKatakana {
     }
 }
 
-// turn jmdict entries into a list of entries with just the id (seq) and written form
+// turn jmdict entries into a list of entries with just the id (seq) and written form, keyed by the written form in katakana
 // since in jmdict entries can have multiple written forms, the list of entries has duplicate ids
-fn process_jmdict(jmdict: JMdict) -> Vec<JMdictWordVerbatim> {
+// also returns the ent_seqs of the entries that were skipped because the ent_seq is not a valid id
+#[allow(clippy::type_complexity)]
+fn process_jmdict(
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+) -> eyre::Result<(Vec<(JMdictWordKatakana, JMdictWordVerbatim)>, Vec<String>)> {
     let mut jmdict_words = vec![];
-    for entry in jmdict.entry {
-        let ent_seq = entry.ent_seq;
-        let id = ent_seq
-            .parse()
-            .unwrap_or_else(|_| panic!("invalid ent_seq {ent_seq}"));
-        if entry.k_ele.is_empty() {
-            for rele in &entry.r_ele {
-                jmdict_words.push(process_entry(id, &entry.sense, None, rele.reb.clone()));
-            }
-        } else {
-            for kele in entry.k_ele {
-                let keb = kele.keb;
-                let mut at_least_one = false;
-                for rele in &entry.r_ele {
-                    if rele.re_restr.is_empty() || rele.re_restr.contains(&keb) {
-                        at_least_one = true;
-                        jmdict_words.push(process_entry(
-                            id,
-                            &entry.sense,
-                            Some(keb.clone()),
-                            rele.reb.clone(),
-                        ));
-                    }
-                }
-                if !at_least_one {
-                    tracing::warn!("keb {} had no applicable readings", keb);
-                }
+    let mut invalid_ent_seqs = vec![];
+    parallel::map_entries(
+        jmdict,
+        |entry| match entry.ent_seq.parse() {
+            Ok(id) => Ok(process_entry(id, entry)),
+            Err(_) => Err(entry.ent_seq),
+        },
+        |processed| match processed {
+            Ok(words) => jmdict_words.extend(words),
+            Err(ent_seq) => invalid_ent_seqs.push(ent_seq),
+        },
+    )?;
+    Ok((jmdict_words, invalid_ent_seqs))
+}
+
+// turns a jmdict entry into entries with just the id (seq), written form and its readings
+fn process_entry(id: JmdictSeq, entry: Entry) -> Vec<(JMdictWordKatakana, JMdictWordVerbatim)> {
+    let mut written_forms = vec![];
+    if entry.k_ele.is_empty() {
+        for rele in entry.r_ele {
+            let readings = BTreeSet::from([rele.reb.clone()]);
+            written_forms.push((rele.reb, readings));
+        }
+    } else {
+        for kele in entry.k_ele {
+            let keb = kele.keb;
+            let readings = entry
+                .r_ele
+                .iter()
+                .filter(|rele| rele.re_restr.is_empty() || rele.re_restr.contains(&keb))
+                .map(|rele| rele.reb.clone())
+                .collect::<BTreeSet<_>>();
+            if !readings.is_empty() {
+                written_forms.push((keb, readings));
+            } else {
+                tracing::warn!("keb {} had no applicable readings", keb);
             }
         }
     }
-    jmdict_words
+    written_forms
+        .into_iter()
+        .map(|(written_form, readings)| {
+            let word = JMdictWordVerbatim {
+                id,
+                written_form,
+                readings,
+            };
+            (JMdictWordKatakana::from_verbatim(&word), word)
+        })
+        .collect()
 }
 
-// turns a jmdict entry into an entry with just the id (seq) and written form
-fn process_entry(id: u32, sense: &[Sense], keb: Option<String>, reb: String) -> JMdictWordVerbatim {
-    let keb = keb.unwrap_or_else(|| reb.clone());
-    let mut meanings = vec![];
-    for s in sense {
-        let stagk = s.stagk.is_empty() || s.stagk.contains(&keb);
-        let stagr = s.stagr.is_empty() || s.stagr.contains(&reb);
-        if stagk && stagr {
-            for g in s.gloss.iter().filter_map(|g| {
-                if g.lang.is_none() {
-                    Some(g.value.clone())
-                } else {
-                    None
-                }
-            }) {
-                meanings.push(g);
-            }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        input::jmdict::{KEle, REle},
+        output::report::Reassignment,
+    };
+
+    fn header(revision: &str) -> jmdict::Header {
+        jmdict::Header {
+            revision: revision.to_string(),
+            date_of_creation: "2023-12-01".to_string(),
+            entry_count: None,
         }
     }
-    JMdictWordVerbatim {
-        id,
-        written_form: keb,
+
+    fn entry(ent_seq: &str, kebs: &[&str], rebs: &[&str]) -> eyre::Result<Entry> {
+        Ok(Entry {
+            ent_seq: ent_seq.to_string(),
+            k_ele: kebs
+                .iter()
+                .map(|keb| KEle {
+                    keb: keb.to_string(),
+                    ke_inf: vec![],
+                    ke_pri: vec![],
+                })
+                .collect(),
+            r_ele: rebs
+                .iter()
+                .map(|reb| REle {
+                    reb: reb.to_string(),
+                    re_nokanji: None,
+                    re_restr: vec![],
+                    re_inf: vec![],
+                    re_pri: vec![],
+                })
+                .collect(),
+            sense: vec![],
+        })
+    }
+
+    fn skeleton(jmdict: Vec<eyre::Result<Entry>>) -> Wordfile {
+        let (skeleton, _) = create(jmdict, header("1"), &[], &mut Errors::new()).unwrap();
+        skeleton
+    }
+
+    #[test]
+    fn invalid_ent_seq_is_skipped_when_creating() {
+        let mut errors = Errors::new();
+        let (skeleton, _) = create(
+            vec![
+                entry("1000010", &["船"], &["ふね"]),
+                entry("invalid", &["舟"], &["ふね"]),
+            ],
+            header("1"),
+            &[],
+            &mut errors,
+        )
+        .unwrap();
+        assert!(!errors.is_empty());
+        assert_eq!(skeleton.words.len(), 1);
+    }
+
+    #[test]
+    fn invalid_ent_seq_does_not_remove_words() {
+        let mut skeleton = skeleton(vec![
+            entry("1000010", &["船"], &["ふね"]),
+            entry("1000020", &["舟"], &["ふね"]),
+        ]);
+        let res = update(
+            &mut skeleton,
+            vec![
+                entry("1000010", &["船"], &["ふね"]),
+                entry("invalid", &["舟"], &["ふね"]),
+            ],
+            header("2"),
+            &[],
+            &[],
+            &mut Errors::new(),
+        );
+        assert!(res.is_err());
+        assert_eq!(skeleton.words.len(), 2);
+        assert!(skeleton.header.tombstones.is_empty());
+    }
+
+    fn id_of(skeleton: &Wordfile, jmdict_id: u32) -> WordId {
+        skeleton
+            .words
+            .iter()
+            .find(|w| w.jmdict_id == Some(JmdictSeq(jmdict_id)))
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn moved_written_form_keeps_id() {
+        let mut skeleton = skeleton(vec![
+            entry("1000010", &["船"], &["ふね"]),
+            entry("1000020", &["舟"], &["ふね"]),
+        ]);
+        let id = id_of(&skeleton, 1000010);
+        let report = update(
+            &mut skeleton,
+            vec![
+                entry("1000020", &["舟"], &["ふね"]),
+                entry("1000030", &["船"], &["ふね"]),
+            ],
+            header("2"),
+            &[],
+            &[],
+            &mut Errors::new(),
+        )
+        .unwrap();
+        assert_eq!(id_of(&skeleton, 1000030), id);
+        assert_eq!(report.reassigned[0].reason, Reassignment::Moved);
+        assert!(report.added.is_empty());
+        assert!(skeleton.header.tombstones.is_empty());
+    }
+
+    #[test]
+    fn split_entry_is_resolved_by_reading() {
+        let mut skeleton = skeleton(vec![entry("1000010", &["船", "舟"], &["ふね"])]);
+        let word = |skeleton: &Wordfile| {
+            skeleton
+                .words
+                .iter()
+                .find(|w| w.written_forms == ["船"])
+                .map(|w| (w.id, w.jmdict_id))
+                .unwrap()
+        };
+        let (id, _) = word(&skeleton);
+        // 船 is split out of the entry into one entry for each of its readings
+        let report = update(
+            &mut skeleton,
+            vec![
+                entry("1000010", &["舟"], &["ふね"]),
+                entry("1000030", &["船"], &["せん"]),
+                entry("1000040", &["船"], &["ふね"]),
+            ],
+            header("2"),
+            &[],
+            &[],
+            &mut Errors::new(),
+        )
+        .unwrap();
+        assert_eq!(report.reassigned[0].reason, Reassignment::Moved);
+        assert_eq!(report.added.len(), 1);
+        assert!(skeleton.header.tombstones.is_empty());
+        assert_eq!(word(&skeleton), (id, Some(JmdictSeq(1000040))));
+    }
+
+    #[test]
+    fn merged_entry_is_succeeded_by_existing_word() {
+        let mut skeleton = skeleton(vec![
+            entry("1000010", &["船"], &["ふね"]),
+            entry("1000020", &["船"], &["せん"]),
+        ]);
+        let removed = id_of(&skeleton, 1000010);
+        let successor = id_of(&skeleton, 1000020);
+        let report = update(
+            &mut skeleton,
+            vec![entry("1000020", &["船"], &["ふね", "せん"])],
+            header("2"),
+            &[],
+            &[],
+            &mut Errors::new(),
+        )
+        .unwrap();
+        assert!(report.reassigned.is_empty());
+        assert_eq!(skeleton.words.len(), 1);
+        let tombstone = &skeleton.header.tombstones[0];
+        assert_eq!(tombstone.id, removed);
+        assert_eq!(tombstone.successor_id, Some(successor));
+        assert_eq!(report.removed[0].successor_id, Some(successor));
+    }
+
+    #[test]
+    fn running_out_of_word_ids_fails() {
+        let mut skeleton = skeleton(vec![entry("1000010", &["船"], &["ふね"])]);
+        skeleton.header.last_word_id = WordId(u32::MAX);
+        let res = update(
+            &mut skeleton,
+            vec![
+                entry("1000010", &["船"], &["ふね"]),
+                entry("1000020", &["舟"], &["ふね"]),
+            ],
+            header("2"),
+            &[],
+            &[],
+            &mut Errors::new(),
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn curated_word_without_written_forms_is_rejected() {
+        let res = serde_json::from_str::<Vec<CuratedWord>>(r#"[{ "written_forms": [] }]"#);
+        assert!(res.is_err());
+        let curated =
+            serde_json::from_str::<Vec<CuratedWord>>(r#"[{ "written_forms": ["ググる"] }]"#)
+                .unwrap();
+        assert_eq!(curated[0].written_forms, ["ググる"]);
+    }
+
+    #[test]
+    fn running_out_of_word_ids_for_curated_words_fails() {
+        let mut skeleton = skeleton(vec![entry("1000010", &["船"], &["ふね"])]);
+        skeleton.header.last_word_id = WordId(u32::MAX);
+        let curated = [CuratedWord {
+            written_forms: vec!["ググる".to_string()],
+            meanings: vec![],
+            readings: vec![],
+        }];
+        let res = update(
+            &mut skeleton,
+            vec![entry("1000010", &["船"], &["ふね"])],
+            header("2"),
+            &[],
+            &curated,
+            &mut Errors::new(),
+        );
+        assert!(res.is_err());
     }
 }
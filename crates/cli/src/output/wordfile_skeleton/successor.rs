@@ -0,0 +1,429 @@
+//! Detects the successors of words removed from the wordfile skeleton, so that data referring to them is not lost.
+//!
+//! A removed word is carried forward, keeping its id, to a new word if
+//! - the new word has the same written form in a different JMdict entry, as happens when entries are merged or split, or
+//! - the new word is in the same JMdict entry and its written form looks like an edited version of the removed one.
+//!
+//! When a `keb` is edited, for example to fix a typo or change the okurigana, the old written form disappears
+//! and a new one appears within the same JMdict entry. Such a pair is considered a rename if
+//! - the new written form has the same readings the old one is expected to have,
+//! - both written forms have the same length when converted to katakana and
+//! - the edit distance between them is small.
+//!
+//! The skeleton does not store readings, so the readings of the old written form are estimated as the ones
+//! shared by all the written forms of the entry in the new JMdict.
+//! When a written form moves to multiple entries, as when an entry is split by reading, the entries with
+//! the estimated readings are preferred, and the written form alone decides when the readings match none of them.
+//!
+//! Removed words that are not carried forward get the existing word with the same written form in a different entry
+//! as their successor, as happens when an entry is merged into one that already had the written form.
+//!
+//! Each decision is logged, and the detection can be overridden manually for each removed word.
+
+use crate::{
+    error::{Error, Errors},
+    output::report::Reassignment,
+};
+use jadata::{
+    id::{JmdictSeq, WordId},
+    wordfile::Word,
+};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use wana_kana::ConvertJapanese;
+
+/// The largest edit distance between the old and new written form that is considered a rename.
+const MAX_EDIT_DISTANCE: usize = 2;
+
+/// A manual decision on the successor of a removed word, overriding the automatic detection.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuccessorOverride {
+    /// The id of the removed word.
+    pub id: WordId,
+    /// A written form of the new word that takes over the id,
+    /// or `None` if the word should not have a successor.
+    pub written_form: Option<String>,
+}
+
+/// A word that was added in the new JMdict.
+pub struct NewWord<'a> {
+    pub jmdict_id: JmdictSeq,
+    pub written_forms: &'a [String],
+    pub readings: &'a BTreeSet<String>,
+}
+
+/// The successors of the removed words.
+#[derive(Debug, Default)]
+pub struct Successors {
+    /// The ids carried forward to new words and the reason, keyed by the index of the new word.
+    pub carried: HashMap<usize, (WordId, Reassignment)>,
+    /// The existing words that succeed removed words, keyed by the id of the removed word.
+    pub existing: HashMap<WordId, WordId>,
+}
+
+/// Matches the removed words to the new or remaining words that replace them.
+/// `shared_readings` contains the readings shared by all the written forms of each JMdict entry.
+pub fn detect(
+    removed: &[Word],
+    new: &[NewWord],
+    remaining: &[Word],
+    shared_readings: &HashMap<JmdictSeq, BTreeSet<String>>,
+    overrides: &[SuccessorOverride],
+    errors: &mut Errors,
+) -> Successors {
+    let overrides = overrides
+        .iter()
+        .map(|o| (o.id, o.written_form.as_deref()))
+        .collect::<HashMap<_, _>>();
+    let mut successors = Successors::default();
+    let mut taken = HashSet::new();
+    // the overrides are handled first so that the automatic detection cannot take their words
+    let (overridden, mut unmatched): (Vec<_>, Vec<_>) =
+        removed.iter().partition(|w| overrides.contains_key(&w.id));
+    for word in overridden {
+        let Some(written_form) = overrides[&word.id] else {
+            tracing::info!(
+                "not carrying word {} {:?} forward due to an override",
+                word.id,
+                word.written_forms
+            );
+            continue;
+        };
+        // a word from the same entry is preferred, but words can also be moved to other entries manually
+        let position = new
+            .iter()
+            .position(|n| {
+                Some(n.jmdict_id) == word.jmdict_id
+                    && n.written_forms.iter().any(|w| w == written_form)
+            })
+            .or_else(|| {
+                new.iter()
+                    .position(|n| n.written_forms.iter().any(|w| w == written_form))
+            });
+        match position {
+            Some(idx) if taken.insert(idx) => {
+                tracing::info!(
+                    "carrying word {} {:?} forward to {:?} due to an override",
+                    word.id,
+                    word.written_forms,
+                    new[idx].written_forms
+                );
+                successors
+                    .carried
+                    .insert(idx, (word.id, Reassignment::Override));
+            }
+            _ => errors.push(Error::UnknownSuccessorOverride {
+                id: word.id,
+                written_form: written_form.to_string(),
+            }),
+        }
+    }
+
+    // written forms that moved to another entry
+    unmatched.retain(|word| {
+        let candidates = new
+            .iter()
+            .enumerate()
+            .filter(|(idx, n)| {
+                Some(n.jmdict_id) != word.jmdict_id
+                    && !taken.contains(idx)
+                    && shares_written_form(n.written_forms, &word.written_forms)
+            })
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        let readings = word.jmdict_id.and_then(|id| shared_readings.get(&id));
+        let with_readings = candidates
+            .iter()
+            .copied()
+            .filter(|idx| readings.is_some_and(|r| !new[*idx].readings.is_disjoint(r)))
+            .collect::<Vec<_>>();
+        let candidates = if with_readings.is_empty() {
+            candidates
+        } else {
+            with_readings
+        };
+        match candidates.as_slice() {
+            [] => true,
+            [idx] => {
+                tracing::info!(
+                    "moving word {} {:?} from JMdict entry {} to {}",
+                    word.id,
+                    word.written_forms,
+                    display_jmdict_id(word.jmdict_id),
+                    new[*idx].jmdict_id
+                );
+                taken.insert(*idx);
+                successors
+                    .carried
+                    .insert(*idx, (word.id, Reassignment::Moved));
+                false
+            }
+            _ => {
+                tracing::warn!(
+                    "not moving word {} {:?}, its written form moved to multiple JMdict entries: {:?}",
+                    word.id,
+                    word.written_forms,
+                    candidates
+                        .iter()
+                        .map(|idx| new[*idx].jmdict_id)
+                        .collect::<Vec<_>>()
+                );
+                true
+            }
+        }
+    });
+
+    // written forms that were edited within the same entry
+    unmatched.retain(|word| {
+        let Some(jmdict_id) = word.jmdict_id else {
+            return true;
+        };
+        let old = word.written_forms[0].to_katakana();
+        let old_len = old.chars().count();
+        let readings = shared_readings.get(&jmdict_id);
+        let mut candidates = new
+            .iter()
+            .enumerate()
+            .filter(|(idx, n)| n.jmdict_id == jmdict_id && !taken.contains(idx))
+            .filter(|(_, n)| Some(n.readings) == readings)
+            .filter_map(|(idx, n)| {
+                let new = n.written_forms[0].to_katakana();
+                if new.chars().count() != old_len {
+                    return None;
+                }
+                let distance = edit_distance(&old, &new);
+                (distance <= MAX_EDIT_DISTANCE && distance < old_len).then_some((idx, distance))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(_, distance)| *distance);
+        match candidates.as_slice() {
+            [] => true,
+            [(_, first), (_, second), ..] if first == second => {
+                tracing::warn!(
+                    "not carrying word {} {:?} forward, it has multiple equally likely successors: {:?}",
+                    word.id,
+                    word.written_forms,
+                    candidates
+                        .iter()
+                        .filter(|(_, distance)| distance == first)
+                        .map(|(idx, _)| new[*idx].written_forms)
+                        .collect::<Vec<_>>()
+                );
+                true
+            }
+            [(idx, distance), ..] => {
+                tracing::info!(
+                    "carrying word {} {:?} forward to {:?} (edit distance {distance})",
+                    word.id,
+                    word.written_forms,
+                    new[*idx].written_forms
+                );
+                taken.insert(*idx);
+                successors
+                    .carried
+                    .insert(*idx, (word.id, Reassignment::Renamed));
+                false
+            }
+        }
+    });
+
+    // entries merged into one that already had the written form
+    for word in unmatched {
+        let candidates = remaining
+            .iter()
+            .filter(|r| {
+                r.jmdict_id.is_some()
+                    && r.jmdict_id != word.jmdict_id
+                    && shares_written_form(&r.written_forms, &word.written_forms)
+            })
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [] => {
+                tracing::debug!(
+                    "no successor found for word {} {:?}",
+                    word.id,
+                    word.written_forms
+                );
+            }
+            [successor] => {
+                tracing::info!(
+                    "word {} {:?} from JMdict entry {} is succeeded by word {} in entry {}",
+                    word.id,
+                    word.written_forms,
+                    display_jmdict_id(word.jmdict_id),
+                    successor.id,
+                    display_jmdict_id(successor.jmdict_id)
+                );
+                successors.existing.insert(word.id, successor.id);
+            }
+            _ => {
+                tracing::warn!(
+                    "word {} {:?} has multiple possible successors: {:?}",
+                    word.id,
+                    word.written_forms,
+                    candidates.iter().map(|c| c.id).collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+    successors
+}
+
+fn shares_written_form(a: &[String], b: &[String]) -> bool {
+    a.iter().any(|w| b.contains(w))
+}
+
+fn display_jmdict_id(jmdict_id: Option<JmdictSeq>) -> String {
+    jmdict_id.map(|id| id.to_string()).unwrap_or_default()
+}
+
+// the Levenshtein distance between the two strings in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn word(id: u32, jmdict_id: u32, written_form: &str) -> Word {
+        Word {
+            id: WordId(id),
+            jmdict_id: Some(JmdictSeq(jmdict_id)),
+            written_forms: vec![written_form.to_string()],
+            meanings: vec![],
+            readings: vec![],
+        }
+    }
+
+    fn readings(readings: &[&str]) -> BTreeSet<String> {
+        readings.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn edited_written_form_is_renamed() {
+        let removed = [word(1, 100, "恐い")];
+        let written_forms = ["怖い".to_string()];
+        let kowai = readings(&["こわい"]);
+        let new = [NewWord {
+            jmdict_id: JmdictSeq(100),
+            written_forms: &written_forms,
+            readings: &kowai,
+        }];
+        let shared_readings = HashMap::from([(JmdictSeq(100), kowai.clone())]);
+        let mut errors = Errors::new();
+        let successors = detect(&removed, &new, &[], &shared_readings, &[], &mut errors);
+        assert_eq!(successors.carried[&0], (WordId(1), Reassignment::Renamed));
+        assert!(successors.existing.is_empty());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn equally_likely_renames_are_rejected() {
+        let removed = [word(1, 100, "恐い")];
+        let kowai_forms = ["怖い".to_string()];
+        let other_forms = ["強い".to_string()];
+        let kowai = readings(&["こわい"]);
+        let new = [
+            NewWord {
+                jmdict_id: JmdictSeq(100),
+                written_forms: &kowai_forms,
+                readings: &kowai,
+            },
+            NewWord {
+                jmdict_id: JmdictSeq(100),
+                written_forms: &other_forms,
+                readings: &kowai,
+            },
+        ];
+        let shared_readings = HashMap::from([(JmdictSeq(100), kowai.clone())]);
+        let successors = detect(
+            &removed,
+            &new,
+            &[],
+            &shared_readings,
+            &[],
+            &mut Errors::new(),
+        );
+        assert!(successors.carried.is_empty());
+    }
+
+    #[test]
+    fn rename_with_different_readings_is_rejected() {
+        let removed = [word(1, 100, "恐い")];
+        let written_forms = ["強い".to_string()];
+        let tsuyoi = readings(&["つよい"]);
+        let new = [NewWord {
+            jmdict_id: JmdictSeq(100),
+            written_forms: &written_forms,
+            readings: &tsuyoi,
+        }];
+        let shared_readings = HashMap::from([(JmdictSeq(100), readings(&["こわい"]))]);
+        let successors = detect(
+            &removed,
+            &new,
+            &[],
+            &shared_readings,
+            &[],
+            &mut Errors::new(),
+        );
+        assert!(successors.carried.is_empty());
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        // without the overrides, 恐い would be renamed to 怖い
+        let removed = [word(1, 100, "恐い"), word(2, 100, "脅い")];
+        let written_forms = ["怖い".to_string()];
+        let kowai = readings(&["こわい"]);
+        let new = [NewWord {
+            jmdict_id: JmdictSeq(100),
+            written_forms: &written_forms,
+            readings: &kowai,
+        }];
+        let shared_readings = HashMap::from([(JmdictSeq(100), kowai.clone())]);
+        let overrides = [SuccessorOverride {
+            id: WordId(2),
+            written_form: Some("怖い".to_string()),
+        }];
+        let mut errors = Errors::new();
+        let successors = detect(
+            &removed,
+            &new,
+            &[],
+            &shared_readings,
+            &overrides,
+            &mut errors,
+        );
+        assert_eq!(successors.carried.len(), 1);
+        assert_eq!(successors.carried[&0], (WordId(2), Reassignment::Override));
+        assert!(errors.is_empty());
+
+        // an override without a written form prevents the rename
+        let overrides = [SuccessorOverride {
+            id: WordId(1),
+            written_form: None,
+        }];
+        let successors = detect(
+            &removed[..1],
+            &new,
+            &[],
+            &shared_readings,
+            &overrides,
+            &mut errors,
+        );
+        assert!(successors.carried.is_empty());
+        assert!(errors.is_empty());
+    }
+}
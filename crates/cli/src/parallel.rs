@@ -0,0 +1,122 @@
+//! Processes JMdict entries in parallel when the `parallel` feature is enabled.
+//!
+//! The entries are parsed one at a time, so they are gathered into batches that are then processed in parallel.
+//! The results are always handled in the order of the entries, so the output does not depend on the feature.
+
+use crate::input::jmdict::Entry;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// The number of entries processed in parallel at a time.
+/// Large enough to keep all threads busy, small enough to not hold too much of JMdict in memory.
+#[cfg(feature = "parallel")]
+const BATCH_SIZE: usize = 4096;
+
+/// Maps each entry with `process` and passes the results to `handle` in the order of the entries.
+#[cfg(feature = "parallel")]
+pub fn map_entries<T, P, H>(
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+    process: P,
+    mut handle: H,
+) -> eyre::Result<()>
+where
+    T: Send,
+    P: Fn(Entry) -> T + Sync,
+    H: FnMut(T),
+{
+    let mut jmdict = jmdict.into_iter();
+    loop {
+        let batch = jmdict
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<eyre::Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let results = batch.into_par_iter().map(&process).collect::<Vec<_>>();
+        results.into_iter().for_each(&mut handle);
+    }
+}
+
+/// Maps each entry with `process` and passes the results to `handle` in the order of the entries.
+#[cfg(not(feature = "parallel"))]
+pub fn map_entries<T, P, H>(
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+    process: P,
+    handle: H,
+) -> eyre::Result<()>
+where
+    P: Fn(Entry) -> T,
+    H: FnMut(T),
+{
+    map_entries_sequentially(jmdict, process, handle)
+}
+
+// also compiled for the tests so that the parallel version can be compared with it
+#[cfg(any(test, not(feature = "parallel")))]
+fn map_entries_sequentially<T, P, H>(
+    jmdict: impl IntoIterator<Item = eyre::Result<Entry>>,
+    process: P,
+    mut handle: H,
+) -> eyre::Result<()>
+where
+    P: Fn(Entry) -> T,
+    H: FnMut(T),
+{
+    for entry in jmdict {
+        handle(process(entry?));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::jmdict::REle;
+
+    // enough entries for a few batches, the last of which is not full
+    const ENTRY_COUNT: usize = 3 * 4096 + 100;
+
+    fn entries() -> impl Iterator<Item = eyre::Result<Entry>> {
+        (0..ENTRY_COUNT).map(|seq| {
+            Ok(Entry {
+                ent_seq: seq.to_string(),
+                k_ele: vec![],
+                r_ele: vec![REle {
+                    reb: "ふね".repeat(seq % 7 + 1),
+                    re_nokanji: None,
+                    re_restr: vec![],
+                    re_inf: vec![],
+                    re_pri: vec![],
+                }],
+                sense: vec![],
+            })
+        })
+    }
+
+    fn process(entry: Entry) -> (String, usize) {
+        (entry.ent_seq, entry.r_ele[0].reb.chars().count())
+    }
+
+    #[test]
+    fn results_are_handled_in_order() {
+        let mut results = vec![];
+        map_entries(entries(), process, |result| results.push(result)).unwrap();
+        let mut sequential = vec![];
+        map_entries_sequentially(entries(), process, |result| sequential.push(result)).unwrap();
+        assert_eq!(results.len(), ENTRY_COUNT);
+        assert_eq!(results, sequential);
+    }
+
+    #[test]
+    fn error_is_returned() {
+        let failing = entries().map(|entry| match entry {
+            Ok(entry) if entry.ent_seq == "5000" => Err(eyre::eyre!("invalid entry")),
+            entry => entry,
+        });
+        let mut handled = 0;
+        let error = map_entries(failing, process, |_| handled += 1).unwrap_err();
+        assert_eq!(error.to_string(), "invalid entry");
+        assert!(handled <= 5000);
+    }
+}
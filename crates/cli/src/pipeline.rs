@@ -0,0 +1,161 @@
+//! The steps shared by all the commands: loading the sources, transforming them into the outputs and writing the outputs.
+//! Each step is logged as a phase.
+
+use crate::{
+    cli::Format,
+    error::Errors,
+    input::{
+        self, cache::Jmdict, jmdict_furigana::Furigana, kanjidic2::Kanjidic2, kradfile::Kradfile,
+    },
+    output::{
+        overlay::Overlay,
+        report::{self, Report},
+        sink::{self, OutputFile},
+        wordfile_skeleton::{successor::SuccessorOverride, CuratedWord},
+        OutputOptions,
+    },
+    Context,
+};
+use std::path::Path;
+
+/// Runs the steps of a command in phases.
+pub struct Pipeline<'a> {
+    ctx: &'a Context,
+    phases: Phases,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(ctx: &'a Context) -> Self {
+        Self {
+            ctx,
+            phases: Phases::default(),
+        }
+    }
+
+    /// Ends the current phase, if any, and starts the next one.
+    pub fn phase(&mut self, name: &'static str) {
+        self.phases.next(name);
+    }
+
+    /// Loads JMdict, from the cache if enabled.
+    pub fn jmdict(&self, path: &Path) -> eyre::Result<Jmdict> {
+        match &self.ctx.cache {
+            Some(cache) => cache.jmdict(path),
+            None => Jmdict::parse(input::open(path)?),
+        }
+    }
+
+    /// Loads KANJIDIC2, from the cache if enabled.
+    pub fn kanjidic2(&self, path: &Path) -> eyre::Result<Kanjidic2> {
+        let parse = |reader| Ok(serde_xml_rs::from_reader(reader)?);
+        match &self.ctx.cache {
+            Some(cache) => cache.load("kanjidic2", path, parse),
+            None => parse(input::open(path)?),
+        }
+    }
+
+    /// Loads KRADFILE, from the cache if enabled.
+    pub fn kradfile(&self, path: &Path) -> eyre::Result<Kradfile> {
+        match &self.ctx.cache {
+            Some(cache) => cache.load("kradfile", path, Kradfile::from),
+            None => Kradfile::from(input::open(path)?),
+        }
+    }
+
+    /// Loads the JMdict furigana file.
+    pub fn furigana(&self, path: &Path) -> eyre::Result<Vec<Furigana>> {
+        Ok(serde_json::from_reader(input::open(path)?)?)
+    }
+
+    /// Loads the manual successor overrides for removed words, if any.
+    pub fn successor_overrides(&self, path: Option<&Path>) -> eyre::Result<Vec<SuccessorOverride>> {
+        match path {
+            Some(path) => Ok(serde_json::from_reader(input::open(path)?)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Loads a CSV or TSV table of manual annotations.
+    pub fn table(&self, path: &Path) -> eyre::Result<Vec<Vec<String>>> {
+        input::table::read(path, input::open(path)?)
+    }
+
+    /// Loads the curated words that are not in JMdict, if any.
+    pub fn curated_words(&self, path: Option<&Path>) -> eyre::Result<Vec<CuratedWord>> {
+        match path {
+            Some(path) => Ok(serde_json::from_reader(input::open(path)?)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Loads the overlay with manual corrections to the generated files, if any.
+    pub fn overlay(&self, path: Option<&Path>) -> eyre::Result<Option<Overlay>> {
+        match path {
+            Some(path) => Ok(Some(serde_json::from_reader(input::open(path)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads a skeleton file.
+    pub fn skeleton<T: OutputFile>(&self, path: &Path) -> eyre::Result<T> {
+        sink::read(path, Format::Json)
+    }
+
+    /// Loads a generated file.
+    pub fn read<T: OutputFile>(&self, path: &Path, format: Format) -> eyre::Result<T> {
+        sink::read(path, format)
+    }
+
+    /// Runs a transformation as its own phase.
+    /// The entry-level errors it records fail the command unless `keep_going` is set.
+    pub fn transform<T>(
+        &mut self,
+        name: &'static str,
+        transform: impl FnOnce(&mut Errors) -> eyre::Result<T>,
+    ) -> eyre::Result<T> {
+        self.phase(name);
+        let mut errors = Errors::new();
+        let output = transform(&mut errors)?;
+        errors.check(self.ctx.keep_going)?;
+        Ok(output)
+    }
+
+    /// Writes a generated file.
+    pub fn write<T: OutputFile>(
+        &self,
+        file: &T,
+        path: &Path,
+        options: OutputOptions,
+    ) -> eyre::Result<()> {
+        tracing::info!("writing {}", path.display());
+        sink::write(file, path, options)
+    }
+
+    /// Writes a skeleton file.
+    pub fn write_skeleton<T: OutputFile>(&self, skeleton: &T, path: &Path) -> eyre::Result<()> {
+        tracing::info!("writing {}", path.display());
+        sink::write_skeleton(skeleton, path)
+    }
+
+    /// Writes a report, as Markdown if the path ends in `.md` and as JSON otherwise.
+    pub fn write_report(&self, report: &impl Report, path: &Path) -> eyre::Result<()> {
+        tracing::info!("writing {}", path.display());
+        report::write(report, path)
+    }
+}
+
+/// Logs the duration of each phase of a command through `tracing` spans.
+#[derive(Default)]
+struct Phases {
+    current: Option<tracing::span::EnteredSpan>,
+}
+
+impl Phases {
+    /// Ends the current phase, if any, and starts the next one.
+    fn next(&mut self, name: &'static str) {
+        // the previous span needs to be exited first so that it is not the parent of the next one
+        self.current = None;
+        self.current = Some(tracing::info_span!("phase", name).entered());
+        tracing::info!("{name}");
+    }
+}
@@ -0,0 +1,113 @@
+//! Contains helpers for reading compressed kanjifiles and wordfiles.
+//!
+//! The generated files are often distributed compressed, so the readers in this crate
+//! can be given the compressed data directly by wrapping the input with [`decompress`].
+//! The compression is detected from the magic bytes at the start of the data.
+
+use std::io::{self, BufRead};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Wraps the reader in a decoder if it starts with the magic bytes of a supported compression format.
+/// gzip requires the `gzip` feature and zstd the `zstd` feature, uncompressed input is returned unchanged.
+/// Returns an error if the input is compressed with a format whose feature is not enabled.
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let start = reader.fill_buf()?;
+    if start.starts_with(GZIP_MAGIC) {
+        #[cfg(feature = "gzip")]
+        return Ok(Box::new(io::BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        )));
+        #[cfg(not(feature = "gzip"))]
+        return Err(unsupported("gzip"));
+    }
+    if start.starts_with(ZSTD_MAGIC) {
+        #[cfg(feature = "zstd")]
+        return Ok(Box::new(io::BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)?,
+        )));
+        #[cfg(not(feature = "zstd"))]
+        return Err(unsupported("zstd"));
+    }
+    Ok(Box::new(reader))
+}
+
+#[cfg(any(not(feature = "gzip"), not(feature = "zstd")))]
+fn unsupported(format: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("the input is compressed with {format}, but the `{format}` feature of jadata is not enabled"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    const TEXT: &[u8] = r#"{"header":{"version":"2"}}"#.as_bytes();
+
+    fn read(compressed: &[u8]) -> Vec<u8> {
+        let mut buf = vec![];
+        decompress(compressed)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        buf
+    }
+
+    #[cfg(feature = "gzip")]
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn uncompressed_input_is_unchanged() {
+        assert_eq!(read(TEXT), TEXT);
+        assert_eq!(read(b""), b"");
+        // too short to be the magic bytes
+        assert_eq!(read(&ZSTD_MAGIC[..2]), &ZSTD_MAGIC[..2]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_round_trip() {
+        let compressed = gzip(TEXT);
+        assert!(compressed.starts_with(GZIP_MAGIC));
+        assert_eq!(read(&compressed), TEXT);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn concatenated_gzip_members_are_read() {
+        let mut compressed = gzip(&TEXT[..10]);
+        compressed.extend(gzip(&TEXT[10..]));
+        assert_eq!(read(&compressed), TEXT);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_round_trip() {
+        let compressed = zstd::encode_all(TEXT, 3).unwrap();
+        assert!(compressed.starts_with(ZSTD_MAGIC));
+        assert_eq!(read(&compressed), TEXT);
+    }
+
+    #[cfg(all(feature = "gzip", feature = "json"))]
+    #[test]
+    fn version_is_read_from_decompressed_stream() {
+        use crate::wordfile::stream::JsonReader;
+
+        let wordfile =
+            r#"{"header":{"version":"2","jmdict_version":"1.09","last_word_id":0},"words":[]}"#;
+        let compressed = gzip(wordfile.as_bytes());
+        let reader = JsonReader::new(decompress(&compressed[..]).unwrap()).unwrap();
+        assert_eq!(reader.header().version, "2");
+        assert_eq!(reader.header().jmdict_version, "1.09");
+    }
+}
@@ -0,0 +1,69 @@
+//! Contains the identifier types used in the kanjifile and wordfile.
+//! They are serialized as plain integers, and exist to make it harder to mix up the different ids.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    num::ParseIntError,
+    str::FromStr,
+};
+
+macro_rules! id {
+    ($(#[$meta:meta])* $name:ident($inner:ty)) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(pub $inner);
+
+        impl $name {
+            /// Returns the id following this one, or `None` if the id space has been exhausted.
+            pub fn next(self) -> Option<Self> {
+                self.0.checked_add(1).map(Self)
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+
+        impl From<$inner> for $name {
+            fn from(id: $inner) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for $inner {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+    };
+}
+
+id!(
+    /// A stable identifier for a word within the wordfile.
+    WordId(u32)
+);
+id!(
+    /// A stable identifier for a kanji within the kanjifile.
+    /// Older versions of jadata used a `u16`, which postcard encodes the same way
+    /// as a `u32` as long as the value fits, see [`crate::kanjifile::legacy`].
+    KanjiId(u32)
+);
+id!(
+    /// The identifier (`ent_seq`) of an entry within JMdict.
+    /// A single JMdict entry may correspond to several jadata words.
+    JmdictSeq(u32)
+);
@@ -1,6 +1,10 @@
 //! Contains the data types for working with the kanjifile.
 
-use serde::{Deserialize, Serialize};
+pub mod legacy;
+
+use crate::{id::KanjiId, ser};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use std::ops::Range;
 
 /// Models the full contents of the kanjifile.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,35 +20,159 @@ pub struct Header {
     pub version: String,
     /// The version of the kanjidic2 that was used as the base for the kanjifile.
     pub kanjidic2_version: String,
+    /// The creation date of the kanjidic2 that was used as the base for the kanjifile.
+    /// Kanjifiles created before this field was added default to an empty string.
+    #[serde(default)]
+    pub kanjidic2_date_of_creation: String,
+    /// The last kanji id. Used to prevent index reuse if kanji get removed and added.
+    /// Kanjifiles created before this field was added default to 0.
+    #[serde(default)]
+    pub last_kanji_id: KanjiId,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Kanji {
     /// A stable identifier for the kanji within the kanjifile.
     // it would be nice to just use the Unicode code point itself as the id,
     // since all kanji should be just a single codepoint, but it's probably not a big deal either way...
-    pub id: u16,
+    pub id: KanjiId,
     /// The kanji itself.
     // pretty sure this could just be a char, but it doesn't really matter
     pub kanji: String,
     /// The components of the kanji.
     /// Note that these are not canonical or "official", but may be helpful nonetheless.
     #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub components: Vec<String>,
     /// A name of the kanji.
     /// Note that this is name exists only to associate the character with some English name
     /// to help with retaining it in memory and is not in any way official.
     #[serde(default)]
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Whether the name was curated by hand or derived from the meanings.
+    /// `None` if the kanji has no name or the kanjifile was created by an older version.
+    #[serde(default)]
+    pub name_source: Option<NameSource>,
     /// A list of translated meanings for the kanji.
     #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub meanings: Vec<String>,
     /// A list of kanji that are visually similar to this kanji.
     /// For example, 人 and 入 are often confused by learners.
     #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub similar: Vec<String>,
 }
+
+/// The origin of the name of a kanji.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameSource {
+    /// The name was chosen by hand in the kanjifile skeleton.
+    Curated,
+    /// The name was derived from the meanings of the kanji.
+    Derived,
+}
+
+impl Serialize for Kanji {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hr = serializer.is_human_readable();
+        let mut state = serializer.serialize_struct("Kanji", 7)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("kanji", &self.kanji)?;
+        ser::field(
+            &mut state,
+            hr,
+            "components",
+            &self.components,
+            self.components.is_empty(),
+        )?;
+        ser::field(&mut state, hr, "name", &self.name, self.name.is_none())?;
+        ser::field(
+            &mut state,
+            hr,
+            "name_source",
+            &self.name_source,
+            self.name_source.is_none(),
+        )?;
+        ser::field(
+            &mut state,
+            hr,
+            "meanings",
+            &self.meanings,
+            self.meanings.is_empty(),
+        )?;
+        ser::field(
+            &mut state,
+            hr,
+            "similar",
+            &self.similar,
+            self.similar.is_empty(),
+        )?;
+        state.end()
+    }
+}
+
+/// Checks whether the character is in one of the CJK Unified Ideographs blocks.
+pub fn is_kanji(c: char) -> bool {
+    let c = c as u32;
+    CJK.contains(&c)
+        || CJK_EXT_B.contains(&c)
+        || CJK_EXT_C.contains(&c)
+        || CJK_EXT_D.contains(&c)
+        || CJK_EXT_E.contains(&c)
+        || CJK_EXT_F.contains(&c)
+        || CJK_EXT_G.contains(&c)
+        || CJK_EXT_H.contains(&c)
+}
+
+const CJK: Range<u32> = 0x4E00..0x9FFF;
+const CJK_EXT_B: Range<u32> = 0x20000..0x2A6DF;
+const CJK_EXT_C: Range<u32> = 0x2A700..0x2B73F;
+const CJK_EXT_D: Range<u32> = 0x2B740..0x2B81F;
+const CJK_EXT_E: Range<u32> = 0x2B820..0x2CEAF;
+const CJK_EXT_F: Range<u32> = 0x2CEB0..0x2EBEF;
+const CJK_EXT_G: Range<u32> = 0x30000..0x3134F;
+const CJK_EXT_H: Range<u32> = 0x31350..0x323AF;
+
+#[cfg(all(test, feature = "postcard"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_fields_round_trip_postcard() {
+        let kanjifile = Kanjifile {
+            header: Header {
+                version: "1".to_string(),
+                kanjidic2_version: "4".to_string(),
+                kanjidic2_date_of_creation: "".to_string(),
+                last_kanji_id: KanjiId(2),
+            },
+            kanji: vec![
+                Kanji {
+                    id: KanjiId(1),
+                    kanji: "〇".to_string(),
+                    components: vec![],
+                    name: None,
+                    name_source: None,
+                    meanings: vec![],
+                    similar: vec![],
+                },
+                Kanji {
+                    id: KanjiId(2),
+                    kanji: "人".to_string(),
+                    components: vec!["人".to_string()],
+                    name: Some("person".to_string()),
+                    name_source: Some(NameSource::Derived),
+                    meanings: vec!["person".to_string()],
+                    similar: vec!["入".to_string()],
+                },
+            ],
+        };
+        let bytes = postcard::to_stdvec(&kanjifile).unwrap();
+        let read: Kanjifile = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(read.kanji[0].name, None);
+        assert!(read.kanji[0].components.is_empty());
+        assert_eq!(read.kanji[1].name.as_deref(), Some("person"));
+        assert_eq!(read.kanji[1].name_source, Some(NameSource::Derived));
+        assert_eq!(read.kanji[1].similar, ["入"]);
+        assert_eq!(postcard::to_stdvec(&read).unwrap(), bytes);
+    }
+}
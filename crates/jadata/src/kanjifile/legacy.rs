@@ -0,0 +1,202 @@
+//! Contains the data types for serving kanjifiles to consumers built against older versions of jadata.
+//!
+//! Older kanjifiles did not have a `last_kanji_id` or `kanjidic2_date_of_creation` in the header,
+//! did not mark the source of kanji names and used `u16` ids.
+//! JSON kanjifiles created by older versions can be deserialized directly into a [`Kanjifile`].
+//! Postcard is not self-describing, and older versions left out empty fields even in postcard,
+//! so old postcard kanjifiles cannot be decoded reliably and need to be generated again.
+//!
+//! A [`LegacyKanjifile`] has the layout the older types expect, with every field present,
+//! so consumers that have not been updated can be served one as long as all the ids still fit in a `u16`.
+
+use super::{Header, Kanji, Kanjifile};
+use crate::id::KanjiId;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// Models the full contents of a kanjifile created by an older version of jadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyKanjifile {
+    pub header: LegacyHeader,
+    pub kanji: Vec<LegacyKanji>,
+}
+
+/// Contains metadata about a kanjifile created by an older version of jadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyHeader {
+    /// The version of the kanjifile.
+    pub version: String,
+    /// The version of the kanjidic2 that was used as the base for the kanjifile.
+    pub kanjidic2_version: String,
+}
+
+/// A kanji in a kanjifile created by an older version of jadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyKanji {
+    pub id: KanjiId,
+    pub kanji: String,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub meanings: Vec<String>,
+    #[serde(default)]
+    pub similar: Vec<String>,
+}
+
+impl From<LegacyKanji> for Kanji {
+    fn from(legacy: LegacyKanji) -> Self {
+        Self {
+            id: legacy.id,
+            kanji: legacy.kanji,
+            components: legacy.components,
+            name: legacy.name,
+            // the source of the name is not known
+            name_source: None,
+            meanings: legacy.meanings,
+            similar: legacy.similar,
+        }
+    }
+}
+
+impl From<Kanji> for LegacyKanji {
+    fn from(kanji: Kanji) -> Self {
+        Self {
+            id: kanji.id,
+            kanji: kanji.kanji,
+            components: kanji.components,
+            name: kanji.name,
+            meanings: kanji.meanings,
+            similar: kanji.similar,
+        }
+    }
+}
+
+impl From<LegacyKanjifile> for Kanjifile {
+    fn from(legacy: LegacyKanjifile) -> Self {
+        let last_kanji_id = legacy.kanji.iter().map(|k| k.id).max().unwrap_or_default();
+        Self {
+            header: Header {
+                version: legacy.header.version,
+                kanjidic2_version: legacy.header.kanjidic2_version,
+                kanjidic2_date_of_creation: String::new(),
+                last_kanji_id,
+            },
+            kanji: legacy.kanji.into_iter().map(Kanji::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<Kanjifile> for LegacyKanjifile {
+    type Error = IdOverflow;
+
+    fn try_from(kanjifile: Kanjifile) -> Result<Self, Self::Error> {
+        if let Some(kanji) = kanjifile
+            .kanji
+            .iter()
+            .find(|k| k.id.0 > u32::from(u16::MAX))
+        {
+            return Err(IdOverflow(kanji.id));
+        }
+        Ok(Self {
+            header: LegacyHeader {
+                version: kanjifile.header.version,
+                kanjidic2_version: kanjifile.header.kanjidic2_version,
+            },
+            kanji: kanjifile.kanji.into_iter().map(LegacyKanji::from).collect(),
+        })
+    }
+}
+
+/// Returned when a kanjifile contains an id that does not fit in the legacy `u16` ids.
+#[derive(Debug, Clone, Copy)]
+pub struct IdOverflow(pub KanjiId);
+
+impl Display for IdOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "kanji id {} does not fit in a legacy kanjifile", self.0)
+    }
+}
+
+impl std::error::Error for IdOverflow {}
+
+#[cfg(all(test, feature = "postcard"))]
+mod test {
+    use super::*;
+    use crate::kanjifile::NameSource;
+
+    // the types of the kanjifile before the ids were widened
+    #[derive(Deserialize)]
+    struct OldKanjifile {
+        header: OldHeader,
+        kanji: Vec<OldKanji>,
+    }
+
+    #[derive(Deserialize)]
+    struct OldHeader {
+        version: String,
+        kanjidic2_version: String,
+    }
+
+    #[derive(Deserialize)]
+    struct OldKanji {
+        id: u16,
+        kanji: String,
+        #[serde(default)]
+        components: Vec<String>,
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        meanings: Vec<String>,
+        #[serde(default)]
+        similar: Vec<String>,
+    }
+
+    fn kanjifile(id: u32) -> Kanjifile {
+        Kanjifile {
+            header: Header {
+                version: "1".to_string(),
+                kanjidic2_version: "4".to_string(),
+                kanjidic2_date_of_creation: "2023-12-01".to_string(),
+                last_kanji_id: KanjiId(id),
+            },
+            kanji: vec![Kanji {
+                id: KanjiId(id),
+                kanji: "日".to_string(),
+                components: vec![],
+                name: Some("sun".to_string()),
+                name_source: Some(NameSource::Curated),
+                meanings: vec!["day".to_string(), "sun".to_string()],
+                similar: vec![],
+            }],
+        }
+    }
+
+    #[test]
+    fn old_types_decode_legacy_layout() {
+        let legacy = LegacyKanjifile::try_from(kanjifile(300)).unwrap();
+        let bytes = postcard::to_stdvec(&legacy).unwrap();
+        let old: OldKanjifile = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(old.header.version, "1");
+        assert_eq!(old.header.kanjidic2_version, "4");
+        let kanji = &old.kanji[0];
+        assert_eq!(kanji.id, 300);
+        assert_eq!(kanji.kanji, "日");
+        assert!(kanji.components.is_empty());
+        assert_eq!(kanji.name.as_deref(), Some("sun"));
+        assert_eq!(kanji.meanings, ["day", "sun"]);
+        assert!(kanji.similar.is_empty());
+
+        let read: LegacyKanjifile = postcard::from_bytes(&bytes).unwrap();
+        let kanjifile = Kanjifile::from(read);
+        assert_eq!(kanjifile.header.last_kanji_id, KanjiId(300));
+        assert_eq!(kanjifile.kanji[0].name_source, None);
+    }
+
+    #[test]
+    fn wide_ids_are_rejected() {
+        let id = u32::from(u16::MAX) + 1;
+        assert!(LegacyKanjifile::try_from(kanjifile(id)).is_err());
+    }
+}
@@ -1,4 +1,9 @@
 //! Contains data types for working with the `kanjifile` and `wordfile`.
 
+pub mod compression;
+pub mod id;
 pub mod kanjifile;
+mod ser;
+#[cfg(feature = "validate")]
+pub mod validate;
 pub mod wordfile;
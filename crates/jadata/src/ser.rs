@@ -0,0 +1,24 @@
+//! Helpers for serializing the data types.
+
+use serde::{ser::SerializeStruct, Serialize};
+
+/// Serializes an optional field, skipping it if `skip` is set and the format is human-readable.
+/// Binary formats like postcard are not self-describing and rely on every field being present,
+/// so the fields are never skipped for them.
+pub(crate) fn field<S, T>(
+    state: &mut S,
+    human_readable: bool,
+    key: &'static str,
+    value: &T,
+    skip: bool,
+) -> Result<(), S::Error>
+where
+    S: SerializeStruct,
+    T: Serialize + ?Sized,
+{
+    if human_readable && skip {
+        state.skip_field(key)
+    } else {
+        state.serialize_field(key, value)
+    }
+}
@@ -0,0 +1,555 @@
+//! Contains checks for the invariants of the kanjifile and wordfile.
+
+use crate::{
+    id::{KanjiId, WordId},
+    kanjifile::{self, Kanjifile},
+    wordfile::{Word, Wordfile},
+};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+};
+use wana_kana::ConvertJapanese;
+
+/// A single violation of an invariant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Multiple words share the same id.
+    DuplicateWordId(WordId),
+    /// A word's id is greater than the last word id in the header.
+    WordIdAboveLast { id: WordId, last: WordId },
+    /// A tombstone has the same id as a word or another tombstone.
+    TombstoneIdInUse(WordId),
+    /// A tombstone's successor is neither a word nor another tombstone.
+    UnknownSuccessor { id: WordId, successor: WordId },
+    /// A word has no written forms.
+    EmptyWrittenForms(WordId),
+    /// A word has written forms that are not equivalent when converted to katakana.
+    WrittenFormsNotEquivalent {
+        id: WordId,
+        first: String,
+        other: String,
+    },
+    /// A furigana's start index is greater than its end index.
+    FuriganaInverted {
+        id: WordId,
+        reading: String,
+        start_idx: usize,
+        end_idx: usize,
+    },
+    /// A furigana's indices point past the end of a written form.
+    FuriganaOutOfBounds {
+        id: WordId,
+        written_form: String,
+        reading: String,
+        end_idx: usize,
+    },
+    /// A furigana's indices do not fall on character boundaries of a written form.
+    FuriganaNotOnCharBoundary {
+        id: WordId,
+        written_form: String,
+        reading: String,
+        idx: usize,
+    },
+    /// Multiple kanji share the same id.
+    DuplicateKanjiId(KanjiId),
+    /// A kanji's id is greater than the last kanji id in the header.
+    KanjiIdAboveLast { id: KanjiId, last: KanjiId },
+    /// The same kanji appears multiple times.
+    DuplicateKanji(String),
+    /// A kanji is not a single character.
+    MultiCharacterKanji { id: KanjiId, kanji: String },
+    /// A kanji lists a similar kanji that is not in the kanjifile.
+    UnknownSimilar { id: KanjiId, similar: String },
+    /// A kanji lists a component that is a kanji but not in the kanjifile.
+    UnknownComponent { id: KanjiId, component: String },
+    /// A kanji has the same name as another kanji.
+    DuplicateKanjiName { id: KanjiId, name: String },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateWordId(id) => write!(f, "word id {id} is used by multiple words"),
+            Self::WordIdAboveLast { id, last } => {
+                write!(f, "word id {id} is greater than the last word id {last}")
+            }
+            Self::TombstoneIdInUse(id) => {
+                write!(f, "removed word id {id} is used by another word")
+            }
+            Self::UnknownSuccessor { id, successor } => write!(
+                f,
+                "removed word {id} has the successor {successor}, but it is not in the wordfile"
+            ),
+            Self::EmptyWrittenForms(id) => write!(f, "word {id} has no written forms"),
+            Self::WrittenFormsNotEquivalent { id, first, other } => write!(
+                f,
+                "word {id} has written forms '{first}' and '{other}' which are not equivalent in katakana"
+            ),
+            Self::FuriganaInverted {
+                id,
+                reading,
+                start_idx,
+                end_idx,
+            } => write!(
+                f,
+                "word {id} reading '{reading}' has furigana starting at {start_idx} after its end {end_idx}"
+            ),
+            Self::FuriganaOutOfBounds {
+                id,
+                written_form,
+                reading,
+                end_idx,
+            } => write!(
+                f,
+                "word {id} reading '{reading}' has furigana ending at {end_idx}, past the end of '{written_form}'"
+            ),
+            Self::FuriganaNotOnCharBoundary {
+                id,
+                written_form,
+                reading,
+                idx,
+            } => write!(
+                f,
+                "word {id} reading '{reading}' has a furigana index {idx} that is not on a character boundary of '{written_form}'"
+            ),
+            Self::DuplicateKanjiId(id) => write!(f, "kanji id {id} is used by multiple kanji"),
+            Self::KanjiIdAboveLast { id, last } => {
+                write!(f, "kanji id {id} is greater than the last kanji id {last}")
+            }
+            Self::DuplicateKanji(kanji) => write!(f, "kanji '{kanji}' appears multiple times"),
+            Self::MultiCharacterKanji { id, kanji } => {
+                write!(f, "kanji {id} '{kanji}' is not a single character")
+            }
+            Self::UnknownSimilar { id, similar } => write!(
+                f,
+                "kanji {id} lists '{similar}' as similar, but it is not in the kanjifile"
+            ),
+            Self::UnknownComponent { id, component } => write!(
+                f,
+                "kanji {id} lists '{component}' as a component, but it is not in the kanjifile"
+            ),
+            Self::DuplicateKanjiName { id, name } => {
+                write!(f, "kanji {id} has the name '{name}', which is used by another kanji")
+            }
+        }
+    }
+}
+
+/// Checks the wordfile for violations of its invariants.
+pub fn wordfile(wordfile: &Wordfile) -> Vec<Violation> {
+    let mut violations = vec![];
+    let mut seen_ids = HashSet::new();
+    for word in &wordfile.words {
+        if !seen_ids.insert(word.id) {
+            violations.push(Violation::DuplicateWordId(word.id));
+        }
+        if word.id > wordfile.header.last_word_id {
+            violations.push(Violation::WordIdAboveLast {
+                id: word.id,
+                last: wordfile.header.last_word_id,
+            });
+        }
+        validate_word(word, &mut violations);
+    }
+    for tombstone in &wordfile.header.tombstones {
+        if !seen_ids.insert(tombstone.id) {
+            violations.push(Violation::TombstoneIdInUse(tombstone.id));
+        }
+    }
+    for tombstone in &wordfile.header.tombstones {
+        if let Some(successor) = tombstone.successor_id {
+            if !seen_ids.contains(&successor) {
+                violations.push(Violation::UnknownSuccessor {
+                    id: tombstone.id,
+                    successor,
+                });
+            }
+        }
+    }
+    violations
+}
+
+fn validate_word(word: &Word, violations: &mut Vec<Violation>) {
+    let (first, rest) = match word.written_forms.split_first() {
+        Some(split) => split,
+        None => {
+            violations.push(Violation::EmptyWrittenForms(word.id));
+            return;
+        }
+    };
+    let first_katakana = first.to_katakana();
+    for other in rest {
+        if other.to_katakana() != first_katakana {
+            violations.push(Violation::WrittenFormsNotEquivalent {
+                id: word.id,
+                first: first.clone(),
+                other: other.clone(),
+            });
+        }
+    }
+
+    for reading in &word.readings {
+        for furigana in &reading.furigana {
+            if furigana.start_idx > furigana.end_idx {
+                violations.push(Violation::FuriganaInverted {
+                    id: word.id,
+                    reading: reading.reading.clone(),
+                    start_idx: furigana.start_idx,
+                    end_idx: furigana.end_idx,
+                });
+                continue;
+            }
+            // the written forms are equivalent in katakana,
+            // so the furigana indices should be valid for each of them
+            for written_form in &word.written_forms {
+                if furigana.end_idx > written_form.len() {
+                    violations.push(Violation::FuriganaOutOfBounds {
+                        id: word.id,
+                        written_form: written_form.clone(),
+                        reading: reading.reading.clone(),
+                        end_idx: furigana.end_idx,
+                    });
+                    continue;
+                }
+                for idx in [furigana.start_idx, furigana.end_idx] {
+                    if !written_form.is_char_boundary(idx) {
+                        violations.push(Violation::FuriganaNotOnCharBoundary {
+                            id: word.id,
+                            written_form: written_form.clone(),
+                            reading: reading.reading.clone(),
+                            idx,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Checks the kanjifile for violations of its invariants.
+pub fn kanjifile(kanjifile: &Kanjifile) -> Vec<Violation> {
+    let mut violations = vec![];
+    let all_kanji = kanjifile
+        .kanji
+        .iter()
+        .map(|k| k.kanji.as_str())
+        .collect::<HashSet<_>>();
+    let mut seen_ids = HashSet::new();
+    let mut seen_kanji = HashSet::new();
+    let mut seen_names = HashSet::new();
+    for kanji in &kanjifile.kanji {
+        if !seen_ids.insert(kanji.id) {
+            violations.push(Violation::DuplicateKanjiId(kanji.id));
+        }
+        if let Some(name) = &kanji.name {
+            if !seen_names.insert(name.as_str()) {
+                violations.push(Violation::DuplicateKanjiName {
+                    id: kanji.id,
+                    name: name.clone(),
+                });
+            }
+        }
+        if kanji.id > kanjifile.header.last_kanji_id {
+            violations.push(Violation::KanjiIdAboveLast {
+                id: kanji.id,
+                last: kanjifile.header.last_kanji_id,
+            });
+        }
+        if !seen_kanji.insert(kanji.kanji.as_str()) {
+            violations.push(Violation::DuplicateKanji(kanji.kanji.clone()));
+        }
+        if kanji.kanji.chars().count() != 1 {
+            violations.push(Violation::MultiCharacterKanji {
+                id: kanji.id,
+                kanji: kanji.kanji.clone(),
+            });
+        }
+        for similar in &kanji.similar {
+            if !all_kanji.contains(similar.as_str()) {
+                violations.push(Violation::UnknownSimilar {
+                    id: kanji.id,
+                    similar: similar.clone(),
+                });
+            }
+        }
+        // many components are radicals or kana rather than kanji, those are not expected to be in the kanjifile
+        for component in &kanji.components {
+            let mut chars = component.chars();
+            let is_kanji =
+                matches!((chars.next(), chars.next()), (Some(c), None) if kanjifile::is_kanji(c));
+            if is_kanji && !all_kanji.contains(component.as_str()) {
+                violations.push(Violation::UnknownComponent {
+                    id: kanji.id,
+                    component: component.clone(),
+                });
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        id::JmdictSeq,
+        kanjifile::Kanji,
+        wordfile::{Furigana, Reading, Tombstone},
+    };
+
+    fn new_wordfile(last_word_id: u32, words: Vec<Word>) -> Wordfile {
+        Wordfile {
+            header: crate::wordfile::Header {
+                version: "1".to_string(),
+                jmdict_version: "1.09".to_string(),
+                jmdict_date_of_creation: "2023-12-01".to_string(),
+                last_word_id: WordId(last_word_id),
+                tombstones: vec![],
+            },
+            words,
+        }
+    }
+
+    fn word(id: u32, written_forms: &[&str]) -> Word {
+        Word {
+            id: WordId(id),
+            jmdict_id: Some(JmdictSeq(1000000 + id)),
+            written_forms: written_forms.iter().map(|w| w.to_string()).collect(),
+            meanings: vec![],
+            readings: vec![],
+        }
+    }
+
+    fn tombstone(id: u32, successor_id: Option<u32>) -> Tombstone {
+        Tombstone {
+            id: WordId(id),
+            jmdict_id: None,
+            written_forms: vec!["舟".to_string()],
+            removed_in_jmdict_version: "1.09".to_string(),
+            successor_id: successor_id.map(WordId),
+        }
+    }
+
+    // 船 is three bytes long in UTF-8
+    fn with_furigana(start_idx: usize, end_idx: usize) -> Word {
+        Word {
+            readings: vec![Reading {
+                reading: "ふねで".to_string(),
+                furigana: vec![Furigana {
+                    start_idx,
+                    end_idx,
+                    furigana: "ふね".to_string(),
+                }],
+                usually_kana: false,
+            }],
+            ..word(1, &["船で"])
+        }
+    }
+
+    fn new_kanjifile(last_kanji_id: u32, kanji: Vec<Kanji>) -> Kanjifile {
+        Kanjifile {
+            header: kanjifile::Header {
+                version: "1".to_string(),
+                kanjidic2_version: "4".to_string(),
+                kanjidic2_date_of_creation: "2023-12-01".to_string(),
+                last_kanji_id: KanjiId(last_kanji_id),
+            },
+            kanji,
+        }
+    }
+
+    fn kanji(id: u32, kanji: &str) -> Kanji {
+        Kanji {
+            id: KanjiId(id),
+            kanji: kanji.to_string(),
+            components: vec![],
+            name: None,
+            name_source: None,
+            meanings: vec![],
+            similar: vec![],
+        }
+    }
+
+    #[test]
+    fn valid_wordfile() {
+        let mut wf = new_wordfile(3, vec![word(1, &["ふね", "フネ"]), with_furigana(0, 3)]);
+        wf.words[1].id = WordId(2);
+        wf.header.tombstones = vec![tombstone(3, Some(2))];
+        assert_eq!(wordfile(&wf), []);
+    }
+
+    #[test]
+    fn word_ids() {
+        let wf = new_wordfile(
+            1,
+            vec![word(1, &["船"]), word(1, &["舟"]), word(2, &["艦"])],
+        );
+        assert_eq!(
+            wordfile(&wf),
+            [
+                Violation::DuplicateWordId(WordId(1)),
+                Violation::WordIdAboveLast {
+                    id: WordId(2),
+                    last: WordId(1)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tombstones() {
+        let mut wf = new_wordfile(3, vec![word(1, &["船"])]);
+        wf.header.tombstones = vec![tombstone(1, None), tombstone(2, Some(3))];
+        assert_eq!(
+            wordfile(&wf),
+            [
+                Violation::TombstoneIdInUse(WordId(1)),
+                Violation::UnknownSuccessor {
+                    id: WordId(2),
+                    successor: WordId(3)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn written_forms() {
+        let wf = new_wordfile(2, vec![word(1, &[]), word(2, &["船", "舟"])]);
+        assert_eq!(
+            wordfile(&wf),
+            [
+                Violation::EmptyWrittenForms(WordId(1)),
+                Violation::WrittenFormsNotEquivalent {
+                    id: WordId(2),
+                    first: "船".to_string(),
+                    other: "舟".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn furigana_inverted() {
+        let wf = new_wordfile(1, vec![with_furigana(3, 0)]);
+        assert_eq!(
+            wordfile(&wf),
+            [Violation::FuriganaInverted {
+                id: WordId(1),
+                reading: "ふねで".to_string(),
+                start_idx: 3,
+                end_idx: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn furigana_out_of_bounds() {
+        let wf = new_wordfile(1, vec![with_furigana(0, 9)]);
+        assert_eq!(
+            wordfile(&wf),
+            [Violation::FuriganaOutOfBounds {
+                id: WordId(1),
+                written_form: "船で".to_string(),
+                reading: "ふねで".to_string(),
+                end_idx: 9
+            }]
+        );
+    }
+
+    #[test]
+    fn furigana_not_on_char_boundary() {
+        let wf = new_wordfile(1, vec![with_furigana(0, 2)]);
+        assert_eq!(
+            wordfile(&wf),
+            [Violation::FuriganaNotOnCharBoundary {
+                id: WordId(1),
+                written_form: "船で".to_string(),
+                reading: "ふねで".to_string(),
+                idx: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn valid_kanjifile() {
+        let mut kf = new_kanjifile(2, vec![kanji(1, "人"), kanji(2, "入")]);
+        kf.kanji[0].similar = vec!["入".to_string()];
+        // components that are not kanji, such as katakana, are not expected in the kanjifile
+        kf.kanji[1].components = vec!["人".to_string(), "ノ".to_string()];
+        kf.kanji[0].name = Some("person".to_string());
+        kf.kanji[1].name = Some("enter".to_string());
+        assert_eq!(kanjifile(&kf), []);
+    }
+
+    #[test]
+    fn kanji_ids() {
+        let kf = new_kanjifile(1, vec![kanji(1, "人"), kanji(1, "入"), kanji(2, "大")]);
+        assert_eq!(
+            kanjifile(&kf),
+            [
+                Violation::DuplicateKanjiId(KanjiId(1)),
+                Violation::KanjiIdAboveLast {
+                    id: KanjiId(2),
+                    last: KanjiId(1)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn kanji_characters() {
+        let kf = new_kanjifile(2, vec![kanji(1, "人"), kanji(2, "人"), kanji(3, "人入")]);
+        assert_eq!(
+            kanjifile(&kf)[..2],
+            [
+                Violation::DuplicateKanji("人".to_string()),
+                Violation::KanjiIdAboveLast {
+                    id: KanjiId(3),
+                    last: KanjiId(2)
+                },
+            ]
+        );
+        assert_eq!(
+            kanjifile(&kf)[2],
+            Violation::MultiCharacterKanji {
+                id: KanjiId(3),
+                kanji: "人入".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_similar_and_component() {
+        let mut kf = new_kanjifile(1, vec![kanji(1, "休")]);
+        kf.kanji[0].similar = vec!["体".to_string()];
+        kf.kanji[0].components = vec!["木".to_string(), "ノ".to_string()];
+        assert_eq!(
+            kanjifile(&kf),
+            [
+                Violation::UnknownSimilar {
+                    id: KanjiId(1),
+                    similar: "体".to_string()
+                },
+                Violation::UnknownComponent {
+                    id: KanjiId(1),
+                    component: "木".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_kanji_name() {
+        let mut kf = new_kanjifile(2, vec![kanji(1, "日"), kanji(2, "陽")]);
+        kf.kanji[0].name = Some("sun".to_string());
+        kf.kanji[1].name = Some("sun".to_string());
+        assert_eq!(
+            kanjifile(&kf),
+            [Violation::DuplicateKanjiName {
+                id: KanjiId(2),
+                name: "sun".to_string()
+            }]
+        );
+    }
+}
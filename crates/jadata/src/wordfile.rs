@@ -1,6 +1,14 @@
 //! Contains the data types for working with the wordfile.
 
-use serde::{Deserialize, Serialize};
+pub mod legacy;
+#[cfg(any(feature = "json", feature = "postcard"))]
+pub mod stream;
+
+use crate::{
+    id::{JmdictSeq, WordId},
+    ser,
+};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
 /// Models the full contents of the wordfile.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,16 +24,42 @@ pub struct Header {
     pub version: String,
     /// The version of the JMdict that was used as the base for the wordfile.
     pub jmdict_version: String,
+    /// The creation date of the JMdict that was used as the base for the wordfile.
+    /// Wordfiles created before this field was added default to an empty string.
+    #[serde(default)]
+    pub jmdict_date_of_creation: String,
     /// The last word id. Used to prevent index reuse if words get removed and added.
-    pub last_word_id: u32,
+    pub last_word_id: WordId,
+    /// Words that have been removed, so that data referring to their ids can be migrated.
+    /// Wordfiles created before this field was added default to no tombstones.
+    #[serde(default)]
+    pub tombstones: Vec<Tombstone>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A record of a word that was removed from the wordfile.
+/// The id of a removed word is never reused.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tombstone {
+    /// The id of the removed word.
+    pub id: WordId,
+    /// The identifier (`seq`) for the corresponding word within JMdict at the time of removal, if any.
+    pub jmdict_id: Option<JmdictSeq>,
+    /// The written forms of the word at the time of removal.
+    pub written_forms: Vec<String>,
+    /// The version of the JMdict the word was no longer found in.
+    pub removed_in_jmdict_version: String,
+    /// The id of the word that replaced the removed word, if any.
+    /// Data referring to the removed word can be moved over to the successor.
+    #[serde(default)]
+    pub successor_id: Option<WordId>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Word {
     /// A stable identifier for the words within the wordfile.
-    pub id: u32,
+    pub id: WordId,
     /// The identifier (`seq`) for the corresponding word within JMdict, if any.
-    pub jmdict_id: Option<u32>,
+    pub jmdict_id: Option<JmdictSeq>,
     /// Different written forms for the same word.
     /// The written forms of a single jadata word are all equivalent when converted to katakana.
     /// In other words, though you could consider the words 船 and 舟 to be the same word (as in JMdict),
@@ -33,26 +67,22 @@ pub struct Word {
     pub written_forms: Vec<String>,
     /// English translations for the different meanings of the word.
     #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub meanings: Vec<String>,
     /// Different readings for the same word.
     #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub readings: Vec<Reading>,
 }
 
 /// Information on a single reading for a word.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Reading {
     /// The reading itself in kana.
     pub reading: String,
     /// The reading split into furigana assigned for each kanji section of the word.
     #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub furigana: Vec<Furigana>,
     /// Indicates whether this reading is usually written using kana.
     #[serde(default)]
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub usually_kana: bool,
 }
 
@@ -66,3 +96,70 @@ pub struct Furigana {
     /// The portion of the reading that maps to the section of kanji.
     pub furigana: String,
 }
+
+impl Serialize for Word {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hr = serializer.is_human_readable();
+        let mut state = serializer.serialize_struct("Word", 5)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("jmdict_id", &self.jmdict_id)?;
+        state.serialize_field("written_forms", &self.written_forms)?;
+        ser::field(
+            &mut state,
+            hr,
+            "meanings",
+            &self.meanings,
+            self.meanings.is_empty(),
+        )?;
+        ser::field(
+            &mut state,
+            hr,
+            "readings",
+            &self.readings,
+            self.readings.is_empty(),
+        )?;
+        state.end()
+    }
+}
+
+impl Serialize for Tombstone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hr = serializer.is_human_readable();
+        let mut state = serializer.serialize_struct("Tombstone", 5)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("jmdict_id", &self.jmdict_id)?;
+        state.serialize_field("written_forms", &self.written_forms)?;
+        state.serialize_field("removed_in_jmdict_version", &self.removed_in_jmdict_version)?;
+        ser::field(
+            &mut state,
+            hr,
+            "successor_id",
+            &self.successor_id,
+            self.successor_id.is_none(),
+        )?;
+        state.end()
+    }
+}
+
+impl Serialize for Reading {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hr = serializer.is_human_readable();
+        let mut state = serializer.serialize_struct("Reading", 3)?;
+        state.serialize_field("reading", &self.reading)?;
+        ser::field(
+            &mut state,
+            hr,
+            "furigana",
+            &self.furigana,
+            self.furigana.is_empty(),
+        )?;
+        ser::field(
+            &mut state,
+            hr,
+            "usually_kana",
+            &self.usually_kana,
+            !self.usually_kana,
+        )?;
+        state.end()
+    }
+}
@@ -0,0 +1,163 @@
+//! Contains the data types for serving wordfiles to consumers built against older versions of jadata.
+//!
+//! Older wordfiles did not have the `jmdict_date_of_creation` or `tombstones` in the header.
+//! The words themselves are unchanged, and JSON wordfiles created by older versions can be
+//! deserialized directly into a [`Wordfile`]. Older versions left out empty fields even in postcard,
+//! so their postcard wordfiles cannot be decoded reliably and need to be generated again.
+//!
+//! A [`LegacyWordfile`] has the header the older types expect and every field of the words present,
+//! so consumers that have not been updated can still read it.
+
+use super::{Header, Word, Wordfile};
+use crate::id::WordId;
+use serde::{Deserialize, Serialize};
+
+/// Models the full contents of a wordfile created by an older version of jadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyWordfile {
+    pub header: LegacyHeader,
+    pub words: Vec<Word>,
+}
+
+/// Contains metadata about a wordfile created by an older version of jadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyHeader {
+    /// The version of the wordfile.
+    pub version: String,
+    /// The version of the JMdict that was used as the base for the wordfile.
+    pub jmdict_version: String,
+    /// The last word id. Used to prevent index reuse if words get removed and added.
+    pub last_word_id: WordId,
+}
+
+impl From<LegacyHeader> for Header {
+    fn from(legacy: LegacyHeader) -> Self {
+        Self {
+            version: legacy.version,
+            jmdict_version: legacy.jmdict_version,
+            jmdict_date_of_creation: String::new(),
+            last_word_id: legacy.last_word_id,
+            tombstones: Vec::new(),
+        }
+    }
+}
+
+/// The tombstones are left out, so the ids of removed words can no longer be migrated by legacy consumers.
+impl From<&Header> for LegacyHeader {
+    fn from(header: &Header) -> Self {
+        Self {
+            version: header.version.clone(),
+            jmdict_version: header.jmdict_version.clone(),
+            last_word_id: header.last_word_id,
+        }
+    }
+}
+
+impl From<LegacyWordfile> for Wordfile {
+    fn from(legacy: LegacyWordfile) -> Self {
+        Self {
+            header: legacy.header.into(),
+            words: legacy.words,
+        }
+    }
+}
+
+impl From<Wordfile> for LegacyWordfile {
+    fn from(wordfile: Wordfile) -> Self {
+        Self {
+            header: LegacyHeader::from(&wordfile.header),
+            words: wordfile.words,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "postcard"))]
+mod test {
+    use super::*;
+    use crate::{
+        id::JmdictSeq,
+        wordfile::{Furigana, Reading, Tombstone},
+    };
+
+    // the types of the wordfile before the header was extended
+    #[derive(Deserialize)]
+    struct OldWordfile {
+        header: OldHeader,
+        words: Vec<OldWord>,
+    }
+
+    #[derive(Deserialize)]
+    struct OldHeader {
+        version: String,
+        jmdict_version: String,
+        last_word_id: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct OldWord {
+        id: u32,
+        jmdict_id: Option<u32>,
+        written_forms: Vec<String>,
+        #[serde(default)]
+        meanings: Vec<String>,
+        #[serde(default)]
+        readings: Vec<OldReading>,
+    }
+
+    #[derive(Deserialize)]
+    struct OldReading {
+        reading: String,
+        #[serde(default)]
+        furigana: Vec<Furigana>,
+        #[serde(default)]
+        usually_kana: bool,
+    }
+
+    #[test]
+    fn old_types_decode_legacy_layout() {
+        let wordfile = Wordfile {
+            header: Header {
+                version: "1".to_string(),
+                jmdict_version: "1.09".to_string(),
+                jmdict_date_of_creation: "2023-12-01".to_string(),
+                last_word_id: WordId(3),
+                tombstones: vec![Tombstone {
+                    id: WordId(3),
+                    jmdict_id: None,
+                    written_forms: vec!["舟".to_string()],
+                    removed_in_jmdict_version: "1.09".to_string(),
+                    successor_id: None,
+                }],
+            },
+            words: vec![Word {
+                id: WordId(2),
+                jmdict_id: Some(JmdictSeq(1000010)),
+                written_forms: vec!["船".to_string()],
+                meanings: vec![],
+                readings: vec![Reading {
+                    reading: "ふね".to_string(),
+                    furigana: vec![],
+                    usually_kana: false,
+                }],
+            }],
+        };
+        let bytes = postcard::to_stdvec(&LegacyWordfile::from(wordfile)).unwrap();
+        let old: OldWordfile = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(old.header.version, "1");
+        assert_eq!(old.header.jmdict_version, "1.09");
+        assert_eq!(old.header.last_word_id, 3);
+        let word = &old.words[0];
+        assert_eq!(word.id, 2);
+        assert_eq!(word.jmdict_id, Some(1000010));
+        assert_eq!(word.written_forms, ["船"]);
+        assert!(word.meanings.is_empty());
+        assert_eq!(word.readings[0].reading, "ふね");
+        assert!(word.readings[0].furigana.is_empty());
+        assert!(!word.readings[0].usually_kana);
+
+        let read: LegacyWordfile = postcard::from_bytes(&bytes).unwrap();
+        let wordfile = Wordfile::from(read);
+        assert_eq!(wordfile.header.last_word_id, WordId(3));
+        assert!(wordfile.header.tombstones.is_empty());
+    }
+}
@@ -0,0 +1,781 @@
+//! Contains readers and writers for processing a wordfile one word at a time.
+//!
+//! The full wordfile is large, so when the words are only needed one by one
+//! (for example, when importing them into a database), these types allow doing so
+//! without ever holding more than a single word in memory.
+//!
+//! The readers can be opened directly from a compressed file with `JsonReader::open` and `PostcardReader::open`.
+
+#[cfg(feature = "postcard")]
+use super::legacy::LegacyHeader;
+use super::{Header, Word};
+use crate::compression;
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+/// The size of the scratch buffer used when deserializing postcard data.
+/// Must be large enough to fit all the strings in a single word.
+#[cfg(feature = "postcard")]
+const POSTCARD_SCRATCH_SIZE: usize = 1024 * 1024;
+
+/// An error encountered while streaming a wordfile.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    #[cfg(feature = "postcard")]
+    Postcard(postcard::Error),
+    /// The input was not a valid wordfile.
+    Format(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            #[cfg(feature = "json")]
+            Self::Json(err) => write!(f, "JSON error: {err}"),
+            #[cfg(feature = "postcard")]
+            Self::Postcard(err) => write!(f, "postcard error: {err}"),
+            Self::Format(msg) => write!(f, "invalid wordfile: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            #[cfg(feature = "json")]
+            Self::Json(err) => Some(err),
+            #[cfg(feature = "postcard")]
+            Self::Postcard(err) => Some(err),
+            Self::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl From<postcard::Error> for Error {
+    fn from(err: postcard::Error) -> Self {
+        Self::Postcard(err)
+    }
+}
+
+/// Reads a JSON wordfile one word at a time.
+/// The header is read when the reader is created, and must precede the words in the file.
+#[cfg(feature = "json")]
+pub struct JsonReader<R> {
+    reader: R,
+    header: Header,
+    state: JsonState,
+}
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonState {
+    FirstWord,
+    Words,
+    Done,
+}
+
+#[cfg(feature = "json")]
+impl<R: io::BufRead> JsonReader<R> {
+    /// Reads the header from the given reader and prepares to read the words.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        json::expect(&mut reader, b'{')?;
+        let mut header = None;
+        loop {
+            if json::peek(&mut reader)? == Some(b'}') {
+                reader.consume(1);
+                let header = header.ok_or_else(|| Error::Format("missing header".to_string()))?;
+                return Ok(Self {
+                    reader,
+                    header,
+                    state: JsonState::Done,
+                });
+            }
+            let key = json::key(&mut reader)?;
+            match key.as_str() {
+                "header" => {
+                    header = Some(json::value(&mut reader)?);
+                }
+                "words" => {
+                    let header =
+                        header.ok_or_else(|| Error::Format("words before header".to_string()))?;
+                    json::expect(&mut reader, b'[')?;
+                    return Ok(Self {
+                        reader,
+                        header,
+                        state: JsonState::FirstWord,
+                    });
+                }
+                _ => json::skip_value(&mut reader)?,
+            }
+            json::separator(&mut reader)?;
+        }
+    }
+
+    /// The header of the wordfile.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn next_word(&mut self) -> Result<Option<Word>, Error> {
+        if self.state == JsonState::Done {
+            return Ok(None);
+        }
+        if json::peek(&mut self.reader)? == Some(b']') {
+            self.reader.consume(1);
+            self.state = JsonState::Done;
+            self.finish_object()?;
+            return Ok(None);
+        }
+        if self.state == JsonState::Words {
+            json::expect(&mut self.reader, b',')?;
+        }
+        self.state = JsonState::Words;
+        json::value(&mut self.reader).map(Some)
+    }
+
+    // skips over any remaining fields after the words
+    fn finish_object(&mut self) -> Result<(), Error> {
+        loop {
+            if json::peek(&mut self.reader)? == Some(b'}') {
+                self.reader.consume(1);
+                return Ok(());
+            }
+            json::expect(&mut self.reader, b',')?;
+            json::key(&mut self.reader)?;
+            json::skip_value(&mut self.reader)?;
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl JsonReader<Box<dyn io::BufRead>> {
+    /// Opens the JSON wordfile at the given path, decompressing it if necessary.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(open(path.as_ref())?)
+    }
+}
+
+#[cfg(feature = "json")]
+impl<R: io::BufRead> Iterator for JsonReader<R> {
+    type Item = Result<Word, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.next_word();
+        if res.is_err() {
+            self.state = JsonState::Done;
+        }
+        res.transpose()
+    }
+}
+
+/// Writes a JSON wordfile one word at a time.
+/// The output is identical to serializing the whole wordfile with `serde_json::to_writer_pretty`,
+/// or `serde_json::to_writer` if created with [`JsonWriter::compact`].
+#[cfg(feature = "json")]
+pub struct JsonWriter<W> {
+    writer: W,
+    words_written: usize,
+    pretty: bool,
+}
+
+#[cfg(feature = "json")]
+impl<W: Write> JsonWriter<W> {
+    /// Writes the header to the given writer and prepares to write the words.
+    pub fn new(mut writer: W, header: &Header) -> Result<Self, Error> {
+        writer.write_all(b"{\n  \"header\": ")?;
+        serde_json::to_writer_pretty(json::Indent::new(&mut writer, 2), header)?;
+        writer.write_all(b",\n  \"words\": [")?;
+        Ok(Self {
+            writer,
+            words_written: 0,
+            pretty: true,
+        })
+    }
+
+    /// Writes the header to the given writer and prepares to write the words without any whitespace.
+    pub fn compact(mut writer: W, header: &Header) -> Result<Self, Error> {
+        writer.write_all(b"{\"header\":")?;
+        serde_json::to_writer(&mut writer, header)?;
+        writer.write_all(b",\"words\":[")?;
+        Ok(Self {
+            writer,
+            words_written: 0,
+            pretty: false,
+        })
+    }
+
+    /// Writes a single word.
+    pub fn write(&mut self, word: &Word) -> Result<(), Error> {
+        if self.pretty {
+            if self.words_written == 0 {
+                self.writer.write_all(b"\n    ")?;
+            } else {
+                self.writer.write_all(b",\n    ")?;
+            }
+            serde_json::to_writer_pretty(json::Indent::new(&mut self.writer, 4), word)?;
+        } else {
+            if self.words_written != 0 {
+                self.writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut self.writer, word)?;
+        }
+        self.words_written += 1;
+        Ok(())
+    }
+
+    /// Finishes the wordfile and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if !self.pretty {
+            self.writer.write_all(b"]}")?;
+        } else if self.words_written == 0 {
+            self.writer.write_all(b"]\n}")?;
+        } else {
+            self.writer.write_all(b"\n  ]\n}")?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads a postcard wordfile one word at a time.
+/// The header is read when the reader is created.
+#[cfg(feature = "postcard")]
+pub struct PostcardReader<R> {
+    reader: R,
+    header: Header,
+    remaining: u64,
+    scratch: Vec<u8>,
+}
+
+#[cfg(feature = "postcard")]
+impl<R: io::Read> PostcardReader<R> {
+    /// Reads the header from the given reader and prepares to read the words.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut scratch = vec![0; POSTCARD_SCRATCH_SIZE];
+        let (header, _) = postcard::from_io((&mut reader, &mut scratch[..]))?;
+        // postcard encodes the length of a sequence the same way as a u64
+        let (remaining, _) = postcard::from_io((&mut reader, &mut scratch[..]))?;
+        Ok(Self {
+            reader,
+            header,
+            remaining,
+            scratch,
+        })
+    }
+
+    /// The header of the wordfile.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The number of words that have not been read yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl PostcardReader<Box<dyn io::BufRead>> {
+    /// Opens the postcard wordfile at the given path, decompressing it if necessary.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(open(path.as_ref())?)
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl<R: io::Read> Iterator for PostcardReader<R> {
+    type Item = Result<Word, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        match postcard::from_io((&mut self.reader, &mut self.scratch[..])) {
+            Ok((word, _)) => Some(Ok(word)),
+            Err(err) => {
+                self.remaining = 0;
+                Some(Err(err.into()))
+            }
+        }
+    }
+}
+
+/// Writes a postcard wordfile one word at a time.
+/// The output is identical to serializing the whole wordfile with `postcard`.
+#[cfg(feature = "postcard")]
+pub struct PostcardWriter<W> {
+    writer: W,
+    remaining: u64,
+}
+
+#[cfg(feature = "postcard")]
+impl<W: Write> PostcardWriter<W> {
+    /// Writes the header to the given writer and prepares to write the words.
+    /// The postcard format stores the length of the word list before the words,
+    /// so the amount of words that will be written needs to be known beforehand.
+    pub fn new(writer: W, header: &Header, word_count: usize) -> Result<Self, Error> {
+        Self::with_header(writer, header, word_count)
+    }
+
+    /// Writes the header in the layout of older versions of jadata and prepares to write the words,
+    /// see [`super::legacy`].
+    pub fn legacy(writer: W, header: &LegacyHeader, word_count: usize) -> Result<Self, Error> {
+        Self::with_header(writer, header, word_count)
+    }
+
+    fn with_header(
+        mut writer: W,
+        header: &impl serde::Serialize,
+        word_count: usize,
+    ) -> Result<Self, Error> {
+        postcard::to_io(header, &mut writer)?;
+        let word_count = word_count as u64;
+        postcard::to_io(&word_count, &mut writer)?;
+        Ok(Self {
+            writer,
+            remaining: word_count,
+        })
+    }
+
+    /// Writes a single word.
+    pub fn write(&mut self, word: &Word) -> Result<(), Error> {
+        if self.remaining == 0 {
+            return Err(Error::Format("too many words written".to_string()));
+        }
+        postcard::to_io(word, &mut self.writer)?;
+        self.remaining -= 1;
+        Ok(())
+    }
+
+    /// Finishes the wordfile and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if self.remaining != 0 {
+            return Err(Error::Format(format!(
+                "{} fewer words written than expected",
+                self.remaining
+            )));
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// opens the file for buffered reading, decompressing it if necessary
+fn open(path: &Path) -> Result<Box<dyn io::BufRead>, Error> {
+    let file = File::open(path)?;
+    Ok(compression::decompress(io::BufReader::new(file))?)
+}
+
+// helpers for walking through the top level of a JSON wordfile
+#[cfg(feature = "json")]
+mod json {
+    use super::Error;
+    use serde::de::DeserializeOwned;
+    use std::io::{self, BufRead, Write};
+
+    // skips whitespace and returns the next byte without consuming it
+    pub fn peek<R: BufRead>(reader: &mut R) -> Result<Option<u8>, Error> {
+        loop {
+            let buf = reader.fill_buf()?;
+            match buf.first() {
+                Some(b) if b.is_ascii_whitespace() => reader.consume(1),
+                Some(b) => return Ok(Some(*b)),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    // skips whitespace and consumes the next byte, which is expected to be `expected`
+    pub fn expect<R: BufRead>(reader: &mut R, expected: u8) -> Result<(), Error> {
+        match peek(reader)? {
+            Some(b) if b == expected => {
+                reader.consume(1);
+                Ok(())
+            }
+            Some(b) => Err(Error::Format(format!(
+                "expected '{}', found '{}'",
+                expected as char, b as char
+            ))),
+            None => Err(Error::Format(format!(
+                "expected '{}', found end of file",
+                expected as char
+            ))),
+        }
+    }
+
+    // consumes the separator after an object field, leaving a closing brace in place
+    pub fn separator<R: BufRead>(reader: &mut R) -> Result<(), Error> {
+        match peek(reader)? {
+            Some(b'}') => Ok(()),
+            _ => expect(reader, b','),
+        }
+    }
+
+    // reads an object key and the colon after it
+    pub fn key<R: BufRead>(reader: &mut R) -> Result<String, Error> {
+        if peek(reader)? != Some(b'"') {
+            return Err(Error::Format("expected an object key".to_string()));
+        }
+        let raw = read_string(reader)?;
+        let key = serde_json::from_slice(&raw)?;
+        expect(reader, b':')?;
+        Ok(key)
+    }
+
+    // deserializes a single JSON value
+    // the values we deserialize are all objects, so serde_json won't read past the closing brace
+    pub fn value<R: BufRead, T: DeserializeOwned>(reader: &mut R) -> Result<T, Error> {
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let value = T::deserialize(&mut de)?;
+        Ok(value)
+    }
+
+    // skips over a JSON value of any kind
+    pub fn skip_value<R: BufRead>(reader: &mut R) -> Result<(), Error> {
+        let mut depth = 0usize;
+        loop {
+            let b = match peek(reader)? {
+                Some(b) => b,
+                None => return Err(Error::Format("unexpected end of file".to_string())),
+            };
+            match b {
+                b'"' => {
+                    read_string(reader)?;
+                }
+                b'{' | b'[' => {
+                    reader.consume(1);
+                    depth += 1;
+                }
+                b'}' | b']' if depth > 0 => {
+                    reader.consume(1);
+                    depth -= 1;
+                }
+                b',' | b'}' | b']' if depth == 0 => return Ok(()),
+                _ => reader.consume(1),
+            }
+            if depth == 0 && matches!(b, b'"' | b'}' | b']') {
+                return Ok(());
+            }
+        }
+    }
+
+    // consumes a JSON string and returns its raw bytes including the quotes
+    fn read_string<R: BufRead>(reader: &mut R) -> Result<Vec<u8>, Error> {
+        let mut raw = vec![];
+        let mut escaped = false;
+        let mut first = true;
+        loop {
+            let mut byte = [0];
+            io::Read::read_exact(reader, &mut byte)?;
+            raw.push(byte[0]);
+            match byte[0] {
+                _ if first => first = false,
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => return Ok(raw),
+                _ => {}
+            }
+        }
+    }
+
+    /// Indents every line after the first by the given amount of spaces.
+    pub struct Indent<W> {
+        writer: W,
+        indent: usize,
+    }
+
+    impl<W> Indent<W> {
+        pub fn new(writer: W, indent: usize) -> Self {
+            Self { writer, indent }
+        }
+    }
+
+    impl<W: Write> Write for Indent<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            // serde_json escapes newlines within strings, so any newline is from the formatter
+            for (i, line) in buf.split(|b| *b == b'\n').enumerate() {
+                if i > 0 {
+                    self.writer.write_all(b"\n")?;
+                    for _ in 0..self.indent {
+                        self.writer.write_all(b" ")?;
+                    }
+                }
+                self.writer.write_all(line)?;
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.writer.flush()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod test {
+    use super::*;
+    use crate::{
+        id::{JmdictSeq, WordId},
+        wordfile::{Reading, Wordfile},
+    };
+
+    fn header() -> Header {
+        Header {
+            version: "1".to_string(),
+            jmdict_version: "1.09".to_string(),
+            jmdict_date_of_creation: "2023-12-01".to_string(),
+            last_word_id: WordId(2),
+            tombstones: vec![],
+        }
+    }
+
+    fn words() -> Vec<Word> {
+        vec![
+            Word {
+                id: WordId(1),
+                jmdict_id: Some(JmdictSeq(1000220)),
+                written_forms: vec!["明白".to_string()],
+                meanings: vec![r#"the "obvious" one"#.to_string()],
+                readings: vec![Reading {
+                    reading: "めいはく".to_string(),
+                    furigana: vec![],
+                    usually_kana: false,
+                }],
+            },
+            Word {
+                id: WordId(2),
+                jmdict_id: None,
+                written_forms: vec![r"back\slash".to_string(), "\"}]".to_string()],
+                meanings: vec![],
+                readings: vec![],
+            },
+        ]
+    }
+
+    fn write(header: &Header, words: &[Word], pretty: bool) -> String {
+        let mut writer = if pretty {
+            JsonWriter::new(vec![], header).unwrap()
+        } else {
+            JsonWriter::compact(vec![], header).unwrap()
+        };
+        for word in words {
+            writer.write(word).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    // the types don't implement PartialEq, so they are compared through their JSON representation
+    fn assert_round_trip(json: &str) {
+        let expected = serde_json::from_str::<Wordfile>(json).unwrap();
+        let reader = JsonReader::new(json.as_bytes()).unwrap();
+        let header = serde_json::to_value(reader.header()).unwrap();
+        let words = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(header, serde_json::to_value(&expected.header).unwrap());
+        assert_eq!(
+            serde_json::to_value(&words).unwrap(),
+            serde_json::to_value(&expected.words).unwrap()
+        );
+    }
+
+    #[test]
+    fn pretty_round_trip() {
+        let json = write(&header(), &words(), true);
+        let wordfile = Wordfile {
+            header: header(),
+            words: words(),
+        };
+        assert_eq!(json, serde_json::to_string_pretty(&wordfile).unwrap());
+        assert_round_trip(&json);
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let json = write(&header(), &words(), false);
+        let wordfile = Wordfile {
+            header: header(),
+            words: words(),
+        };
+        assert_eq!(json, serde_json::to_string(&wordfile).unwrap());
+        assert_round_trip(&json);
+    }
+
+    #[test]
+    fn empty_words() {
+        for pretty in [true, false] {
+            let json = write(&header(), &[], pretty);
+            let wordfile = Wordfile {
+                header: header(),
+                words: vec![],
+            };
+            let expected = if pretty {
+                serde_json::to_string_pretty(&wordfile).unwrap()
+            } else {
+                serde_json::to_string(&wordfile).unwrap()
+            };
+            assert_eq!(json, expected);
+            assert_round_trip(&json);
+        }
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let json = r#"{
+            "comment": { "text": "a \"quoted\" \\ value ]}", "list": [1, [2], {}] },
+            "header": {
+                "version": "1",
+                "jmdict_version": "1.09",
+                "last_word_id": 1,
+                "generator": "someone else's tool"
+            },
+            "words": [
+                { "id": 1, "jmdict_id": null, "written_forms": ["\"\\"] }
+            ],
+            "trailer": "}"
+        }"#;
+        assert_round_trip(json);
+    }
+
+    #[test]
+    fn missing_header() {
+        assert!(JsonReader::new(r#"{ "words": [] }"#.as_bytes()).is_err());
+        assert!(JsonReader::new("{}".as_bytes()).is_err());
+    }
+}
+
+#[cfg(all(test, feature = "postcard"))]
+mod postcard_test {
+    use super::*;
+    use crate::{
+        id::{JmdictSeq, WordId},
+        wordfile::{Furigana, Reading, Tombstone, Wordfile},
+    };
+
+    // covers the fields that are left out of JSON when empty, which postcard has to keep
+    fn wordfile() -> Wordfile {
+        Wordfile {
+            header: Header {
+                version: "1".to_string(),
+                jmdict_version: "1.09".to_string(),
+                jmdict_date_of_creation: "".to_string(),
+                last_word_id: WordId(3),
+                tombstones: vec![Tombstone {
+                    id: WordId(3),
+                    jmdict_id: None,
+                    written_forms: vec!["舟".to_string()],
+                    removed_in_jmdict_version: "1.09".to_string(),
+                    successor_id: None,
+                }],
+            },
+            words: vec![
+                Word {
+                    id: WordId(1),
+                    jmdict_id: None,
+                    written_forms: vec!["ググる".to_string()],
+                    meanings: vec![],
+                    readings: vec![Reading {
+                        reading: "ググる".to_string(),
+                        furigana: vec![],
+                        usually_kana: false,
+                    }],
+                },
+                Word {
+                    id: WordId(2),
+                    jmdict_id: Some(JmdictSeq(1000010)),
+                    written_forms: vec!["船".to_string()],
+                    meanings: vec!["ship".to_string()],
+                    readings: vec![Reading {
+                        reading: "ふね".to_string(),
+                        furigana: vec![Furigana {
+                            start_idx: 0,
+                            end_idx: 1,
+                            furigana: "ふね".to_string(),
+                        }],
+                        usually_kana: true,
+                    }],
+                },
+            ],
+        }
+    }
+
+    fn write(wordfile: &Wordfile) -> Vec<u8> {
+        let mut writer =
+            PostcardWriter::new(vec![], &wordfile.header, wordfile.words.len()).unwrap();
+        for word in &wordfile.words {
+            writer.write(word).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let wordfile = wordfile();
+        let bytes = write(&wordfile);
+        assert_eq!(bytes, postcard::to_stdvec(&wordfile).unwrap());
+
+        let reader = PostcardReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.remaining(), 2);
+        let header = reader.header().clone();
+        assert_eq!(header.jmdict_date_of_creation, "");
+        assert_eq!(header.tombstones[0].jmdict_id, None);
+        assert_eq!(header.tombstones[0].successor_id, None);
+        let words = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(words[0].jmdict_id, None);
+        assert!(words[0].meanings.is_empty());
+        assert!(words[0].readings[0].furigana.is_empty());
+        assert!(!words[0].readings[0].usually_kana);
+        assert_eq!(words[1].jmdict_id, Some(JmdictSeq(1000010)));
+        assert_eq!(words[1].readings[0].furigana[0].furigana, "ふね");
+        assert!(words[1].readings[0].usually_kana);
+
+        // the types don't implement PartialEq, so the words read are compared by writing them again
+        let read = Wordfile { header, words };
+        assert_eq!(write(&read), bytes);
+    }
+
+    #[test]
+    fn empty_words() {
+        let wordfile = Wordfile {
+            words: vec![],
+            ..wordfile()
+        };
+        let bytes = write(&wordfile);
+        assert_eq!(bytes, postcard::to_stdvec(&wordfile).unwrap());
+        let mut reader = PostcardReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.remaining(), 0);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn word_count_is_checked() {
+        let wordfile = wordfile();
+        let mut writer = PostcardWriter::new(vec![], &wordfile.header, 1).unwrap();
+        writer.write(&wordfile.words[0]).unwrap();
+        assert!(writer.write(&wordfile.words[1]).is_err());
+        let writer = PostcardWriter::new(vec![], &wordfile.header, 2).unwrap();
+        assert!(writer.finish().is_err());
+    }
+}
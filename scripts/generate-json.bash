@@ -1,6 +1,7 @@
 #!/bin/bash
 
 # Generates the complete files in the JSON format
+# The input and output paths can be changed in jadata.toml, see `cargo run -- config init`
 
 echo "Input the version for the kanjifile:"
 read -r kanjifile_version
@@ -9,18 +10,7 @@ echo "Input the version for the wordfile:"
 read -r wordfile_version
 
 cargo run --release --\
-    kanjifile\
-        -v "$kanjifile_version"\
-        -d ./external/kanjidic2.xml\
-        -k ./external/kradfile\
-        -s ./included/kanjifile_skeleton.json\
-        -t json\
-        -o ./generated/kanjifile.json
-cargo run --release -- \
-    wordfile\
-        -v "$wordfile_version"\
-        -j ./external/JMdict_e_examp.xml\
-        -f ./external/JmdictFuriganaPretty.json\
-        -s ./included/wordfile_skeleton.json\
-        -t json\
-        -o ./generated/wordfile.json
+    build\
+        --kanjifile-version "$kanjifile_version"\
+        --wordfile-version "$wordfile_version"\
+        -t json
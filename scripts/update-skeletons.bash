@@ -1,13 +1,7 @@
 #!/bin/bash
 
-# Updates the skeleton files
+# Updates the skeleton files and writes reports of the changes to ./reports
+# The input and skeleton paths can be changed in jadata.toml, see `cargo run -- config init`
 
-cargo run --release --\
-    kanjifile-skeleton\
-        -d ./external/kanjidic2.xml\
-        -j ./external/JMdict_e_examp.xml\
-        -o ./included/kanjifile_skeleton.json
-cargo run --release -- \
-    wordfile-skeleton\
-        -j ./external/JMdict_e_examp.xml\
-        -o ./included/wordfile_skeleton.json
+cargo run --release -- kanjifile-skeleton --report reports/kanjifile_skeleton_report.md
+cargo run --release -- wordfile-skeleton --report reports/wordfile_skeleton_report.md